@@ -1,28 +1,34 @@
-use r6502::emulator::{
-    bus::{Bus, Device},
-    clock::Clock,
-    cpu::Cpu,
-    display::Display,
-    rom::Rom,
-};
+use r6502::emulator::{bus::Bus, clock::Clock, cpu::Cpu, display::Display, ram::Ram, rom::Rom};
 
 fn main() {
     println!("Hello, world!");
 
-    let mut prog_rom = Rom { mem: [0; 65535] };
     let programm = read_bytes_from_file("./asm/a.out");
-    for (i, v) in programm.iter().enumerate() {
-        prog_rom.mem[i] = *v;
-    }
+    let mut clock = board(programm);
+    let reason = clock.start();
+    println!("\nstopped: {reason}");
+}
+
+// the image covers the address space from 0x0000, the rom only holds the part from 0x0400 on
+// vasm stops the image after the last byte it emits, so it's padded to the full 64K first
+fn board(mut programm: Vec<u8>) -> Clock {
+    programm.resize(0x10000, 0);
+    let prog_rom = Rom::from_bytes(&programm[0x400..]);
 
     let display = Display::new();
 
     let mut bus = Bus::new();
-    bus.attach(Device::Rom(prog_rom), (0x400, 0xFFFF));
-    bus.attach(Device::Display(display), (0x200, 0x200));
+    bus.attach(Box::new(Ram::new(0x200)), (0x0000, 0x01FF))
+        .expect("invalid memory map");
+    bus.attach(Box::new(prog_rom), (0x400, 0xFFFF))
+        .expect("invalid memory map");
+    bus.attach(Box::new(display), (0x200, 0x200))
+        .expect("invalid memory map");
     let cpu = Cpu::new(bus);
     let mut clock = Clock::new(cpu);
-    clock.start();
+    // the programm ends in an idle loop, nothing runs on interrupts
+    clock.trap_halts = true;
+    clock
 }
 
 fn read_bytes_from_file(path: &str) -> Vec<u8> {
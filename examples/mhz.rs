@@ -0,0 +1,42 @@
+// emulated clock speed on a board with a handful of devices, run with
+// cargo run --release --example mhz
+use std::time::Instant;
+
+use r6502::emulator::{bus::Bus, cpu::Cpu, ram::Ram, rom::Rom};
+
+const CYCLES: u64 = 200_000_000;
+
+fn main() {
+    // copies a page of the rom into ram over and over
+    // loop: LDA $E100,X; STA $0200,X; INC $10; INX; BNE loop; JMP loop
+    let mut rom = Rom::new(0x2000);
+    rom.mem[0x0000..0x000F].copy_from_slice(&[
+        0xBD, 0x00, 0xE1, 0x9D, 0x00, 0x02, 0xE6, 0x10, 0xE8, 0xD0, 0xF5, 0x4C, 0x00, 0xE0, 0x00,
+    ]);
+    rom.mem[0x1FFC..].copy_from_slice(&[0x00, 0xE0, 0x00, 0x00]);
+
+    // some io chips in front of the rom, like on most boards
+    let mut bus = Bus::new();
+    bus.attach(Box::new(Ram::new(0x0800)), (0x0000, 0x07FF))
+        .expect("invalid memory map");
+    for io in 0..8 {
+        let start = 0xD000 + io * 0x0100;
+        bus.attach(Box::new(Ram::new(0x0010)), (start, start + 0x000F))
+            .expect("invalid memory map");
+    }
+    bus.attach(Box::new(rom), (0xE000, 0xFFFF))
+        .expect("invalid memory map");
+
+    let mut cpu = Cpu::new(bus);
+    cpu.reset();
+    let start = Instant::now();
+    while cpu.cycles < CYCLES {
+        cpu.step().expect("benchmark programm failed");
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{} cycles in {elapsed:.2}s, {:.1} MHz",
+        cpu.cycles,
+        cpu.cycles as f64 / elapsed / 1_000_000.0
+    );
+}
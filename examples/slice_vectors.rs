@@ -0,0 +1,45 @@
+// copies the first few cases of every opcode from a checkout of the single step vectors
+// into tests/vectors/slice, small enough to be checked in and run on every cargo test
+// cargo run --example slice_vectors -- <path to 65x02 checkout> [cases per opcode]
+use std::path::Path;
+
+use serde_json::Value;
+
+const SETS: [&str; 2] = ["6502", "wdc65c02"];
+const DEFAULT_CASES: usize = 20;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let source = args
+        .next()
+        .expect("usage: slice_vectors <path to 65x02 checkout> [cases per opcode]");
+    let cases = args
+        .next()
+        .map(|arg| arg.parse().expect("cases per opcode is not a number"))
+        .unwrap_or(DEFAULT_CASES);
+
+    let target = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors/slice");
+    for set in SETS {
+        let from = Path::new(&source).join(set).join("v1");
+        let to = target.join(set).join("v1");
+        std::fs::create_dir_all(&to).expect("can't create the slice directory");
+
+        for opt_code in 0..=0xFFu8 {
+            let file = format!("{opt_code:02x}.json");
+            let text = std::fs::read_to_string(from.join(&file))
+                .unwrap_or_else(|err| panic!("can't read {}: {err}", from.join(&file).display()));
+            let vectors: Value = serde_json::from_str(&text).expect("invalid json");
+            let vectors = vectors.as_array().expect("not a list of tests");
+
+            // one case per line keeps the diffs readable
+            let lines: Vec<String> = vectors
+                .iter()
+                .take(cases)
+                .map(|vector| vector.to_string())
+                .collect();
+            std::fs::write(to.join(&file), format!("[\n{}\n]\n", lines.join(",\n")))
+                .expect("can't write the slice");
+        }
+        println!("{set}: {cases} cases per opcode in {}", to.display());
+    }
+}
@@ -0,0 +1,245 @@
+use std::ops::Range;
+
+use crate::emulator::cpu::{BusAccess, Cpu};
+use crate::emulator::instructionset::MemMode;
+
+// how an instruction uses its effective address, indexed modes spend different dummy cycles on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    // the high byte is only fixed up when the index carried into it
+    Read,
+    // the high byte is always fixed up before the address is used
+    Write,
+    // like a write on the nmos 6502, like a read on the 65C02
+    Modify,
+}
+
+// result of running the addressing cycles of an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveAddress {
+    // for IMM it is the address of the operand byte itself, 0 for IMP/ACC
+    pub addr: u16,
+    // indexing carried into the high byte of the address
+    pub page_crossed: bool,
+    // positions of the accesses made while resolving in Cpu::bus_accesses
+    pub accesses: Range<usize>,
+}
+
+impl EffectiveAddress {
+    pub fn accesses<'a>(&self, cpu: &'a Cpu) -> &'a [BusAccess] {
+        &cpu.bus_accesses()[self.accesses.clone()]
+    }
+}
+
+// runs the addressing cycles of the instruction at the programm counter,
+// the effective address itself is not touched
+// everything wraps like on the hardware: the programm counter and 16 bit addresses at 0xFFFF,
+// zero page indexing and pointers within the zero page
+pub fn resolve(cpu: &mut Cpu, mem_mode: MemMode, access: Access) -> EffectiveAddress {
+    let first_access = cpu.bus_accesses().len();
+    let (addr, page_crossed) = addressing_cycles(cpu, mem_mode, access);
+    EffectiveAddress {
+        addr,
+        page_crossed,
+        accesses: first_access..cpu.bus_accesses().len(),
+    }
+}
+
+fn addressing_cycles(cpu: &mut Cpu, mem_mode: MemMode, access: Access) -> (u16, bool) {
+    let operand = cpu.programm_counter.wrapping_add(1);
+
+    match mem_mode {
+        MemMode::IMP | MemMode::ACC => (0, false),
+        MemMode::IMM => (operand, false),
+        MemMode::ZPG | MemMode::ZPGREL => (cpu.read(operand) as u16, false),
+        MemMode::ZPGX | MemMode::ZPGY => {
+            let zpg_addr = cpu.read(operand);
+            // the base address is read once more while the index is added
+            dummy_read(cpu, mem_mode, zpg_addr as u16);
+            let index = if mem_mode == MemMode::ZPGX {
+                cpu.x
+            } else {
+                cpu.y
+            };
+            // indexing wraps around within the zero page
+            (zpg_addr.wrapping_add(index) as u16, false)
+        }
+        MemMode::REL => {
+            // the offset is signed and relative to the instruction following the branch
+            let offset = cpu.read(operand) as i8;
+            let rel_addr = cpu
+                .programm_counter
+                .wrapping_add(2)
+                .wrapping_add(offset as u16);
+            (rel_addr, false)
+        }
+        MemMode::ABS => (read_word(cpu, operand), false),
+        MemMode::ABSX => {
+            let abs_addr = read_word(cpu, operand);
+            indexed(cpu, mem_mode, access, abs_addr, cpu.x)
+        }
+        MemMode::ABSY => {
+            let abs_addr = read_word(cpu, operand);
+            indexed(cpu, mem_mode, access, abs_addr, cpu.y)
+        }
+        MemMode::IND => {
+            let ind_addr = read_word(cpu, operand);
+            // the nmos 6502 doesn't carry into the high byte when fetching the pointer,
+            // so JMP ($xxFF) takes its high byte from $xx00
+            // the 65C02 fixed this at the cost of a cycle
+            let ind_addr_high = if cpu.variant.is_cmos() {
+                cpu.read(operand.wrapping_add(1));
+                ind_addr.wrapping_add(1)
+            } else if cpu.jmp_ind_page_wrap {
+                (ind_addr & 0xFF00) | (ind_addr as u8).wrapping_add(1) as u16
+            } else {
+                ind_addr.wrapping_add(1)
+            };
+            let jump_addr = cpu.read(ind_addr) as u16 | (cpu.read(ind_addr_high) as u16) << 8;
+            (jump_addr, false)
+        }
+        MemMode::IDXIND => {
+            let zpg_addr = cpu.read(operand);
+            dummy_read(cpu, mem_mode, zpg_addr as u16);
+            (read_zpg_pointer(cpu, zpg_addr.wrapping_add(cpu.x)), false)
+        }
+        MemMode::INDIDX => {
+            let zpg_addr = cpu.read(operand);
+            let ind_addr = read_zpg_pointer(cpu, zpg_addr);
+            indexed(cpu, mem_mode, access, ind_addr, cpu.y)
+        }
+        MemMode::ZPGIND => {
+            let zpg_addr = cpu.read(operand);
+            (read_zpg_pointer(cpu, zpg_addr), false)
+        }
+        MemMode::ABSXIND => {
+            let abs_addr = read_word(cpu, operand);
+            cpu.read(operand.wrapping_add(1));
+            let ind_addr = abs_addr.wrapping_add(cpu.x as u16);
+            (read_word(cpu, ind_addr), false)
+        }
+    }
+}
+
+fn read_word(cpu: &mut Cpu, addr: u16) -> u16 {
+    cpu.read(addr) as u16 | (cpu.read(addr.wrapping_add(1)) as u16) << 8
+}
+
+// pointers in the zero page never leave it
+fn read_zpg_pointer(cpu: &mut Cpu, zpg_addr: u8) -> u16 {
+    cpu.read(zpg_addr as u16) as u16 | (cpu.read(zpg_addr.wrapping_add(1) as u16) as u16) << 8
+}
+
+// adding a 16 bit index takes a cycle which reads from the address before the carry is fixed up
+fn indexed(cpu: &mut Cpu, mem_mode: MemMode, access: Access, base: u16, index: u8) -> (u16, bool) {
+    let addr = base.wrapping_add(index as u16);
+    let page_crossed = base & 0xFF00 != addr & 0xFF00;
+    let fix_up = match access {
+        Access::Read => page_crossed,
+        Access::Write => true,
+        Access::Modify => page_crossed || !cpu.variant.is_cmos(),
+    };
+    if fix_up {
+        dummy_read(cpu, mem_mode, (base & 0xFF00) | (addr & 0x00FF));
+    }
+    (addr, page_crossed)
+}
+
+// the 65C02 re-reads the last byte of the instruction instead of a possibly invalid address
+fn dummy_read(cpu: &mut Cpu, mem_mode: MemMode, nmos_addr: u16) {
+    if cpu.variant.is_cmos() {
+        let last_byte = cpu
+            .programm_counter
+            .wrapping_add(mem_mode.bytes() as u16 - 1);
+        cpu.read(last_byte);
+    } else {
+        cpu.read(nmos_addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, Access};
+    use crate::emulator::bus::Bus;
+    use crate::emulator::cpu::{BusAccess, Cpu};
+    use crate::emulator::instructionset::MemMode;
+    use crate::emulator::rom::Rom;
+
+    // the operand bytes follow the opcode at pc, everything else is zero
+    fn cpu_at(pc: u16, operand: &[u8]) -> Cpu {
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Rom::new(0x10000)), (0x0000, 0xFFFF))
+            .unwrap();
+        let mut cpu = Cpu::new(bus);
+        cpu.programm_counter = pc;
+        for (i, byte) in operand.iter().enumerate() {
+            cpu.bus
+                .write_to(pc.wrapping_add(1).wrapping_add(i as u16), *byte);
+        }
+        cpu
+    }
+
+    #[test]
+    fn operand_wraps_at_end_of_memory() {
+        let mut cpu = cpu_at(0xFFFF, &[0x34, 0x12]);
+        let resolved = resolve(&mut cpu, MemMode::ABS, Access::Read);
+        assert_eq!(resolved.addr, 0x1234);
+        assert_eq!(
+            resolved.accesses(&cpu),
+            [BusAccess::Read(0x0000, 0x34), BusAccess::Read(0x0001, 0x12)]
+        );
+    }
+
+    #[test]
+    fn zero_page_index_wraps() {
+        let mut cpu = cpu_at(0x0400, &[0xF0]);
+        cpu.x = 0x20;
+        let resolved = resolve(&mut cpu, MemMode::ZPGX, Access::Read);
+        assert_eq!(resolved.addr, 0x0010);
+        assert!(!resolved.page_crossed);
+        assert_eq!(
+            resolved.accesses(&cpu),
+            [BusAccess::Read(0x0401, 0xF0), BusAccess::Read(0x00F0, 0x00)]
+        );
+    }
+
+    #[test]
+    fn absolute_index_wraps() {
+        let mut cpu = cpu_at(0x0400, &[0xF0, 0xFF]);
+        cpu.y = 0x20;
+        let resolved = resolve(&mut cpu, MemMode::ABSY, Access::Read);
+        assert_eq!(resolved.addr, 0x0010);
+        assert!(resolved.page_crossed);
+        // the dummy read happens before the carry reaches the high byte
+        assert_eq!(resolved.accesses(&cpu)[2], BusAccess::Read(0xFF10, 0x00));
+    }
+
+    #[test]
+    fn page_fix_up_depends_on_access() {
+        for (access, accesses) in [(Access::Read, 2), (Access::Write, 3), (Access::Modify, 3)] {
+            let mut cpu = cpu_at(0x0400, &[0x00, 0x20]);
+            cpu.x = 0x10;
+            let resolved = resolve(&mut cpu, MemMode::ABSX, access);
+            assert_eq!(resolved.addr, 0x2010);
+            assert_eq!(resolved.accesses.len(), accesses, "{access:?}");
+        }
+    }
+
+    #[test]
+    fn pointers_wrap_in_zero_page() {
+        let mut cpu = cpu_at(0x0400, &[0xFF]);
+        cpu.bus.write_to(0x00FF, 0x34);
+        cpu.bus.write_to(0x0000, 0x12);
+        cpu.y = 0x01;
+        let resolved = resolve(&mut cpu, MemMode::INDIDX, Access::Read);
+        assert_eq!(resolved.addr, 0x1235);
+        assert_eq!(
+            resolved.accesses(&cpu),
+            [
+                BusAccess::Read(0x0401, 0xFF),
+                BusAccess::Read(0x00FF, 0x34),
+                BusAccess::Read(0x0000, 0x12),
+            ]
+        );
+    }
+}
@@ -1,64 +1,604 @@
-use crate::emulator::display::Display;
-use crate::emulator::ram::Ram;
-use crate::emulator::rom::Rom;
-
-pub enum Device {
-    Ram(Ram),
-    Rom(Rom),
-    Display(Display),
+use std::fmt;
+
+use crate::emulator::error::{BusError, CpuError};
+use crate::emulator::random::Random;
+
+// anything that can be attached to the bus
+// addr is relative to the start of the window the device is attached to,
+// a device attached at 0xE000 sees a cpu access to 0xE002 at addr 2
+// Send so a whole board can be moved into the thread that runs it
+pub trait BusDevice: Send {
+    // takes &mut self because io chips change state when read, e.g. clearing a status bit
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, data: u8);
+
+    // reads without any side effects, for disassembling and inspecting memory
+    // devices that can't tell without changing state leave the data lines floating
+    fn peek(&self, _addr: u16) -> u8 {
+        0xFF
+    }
+
+    // called once for every cpu cycle
+    fn tick(&mut self) {}
+
+    // called when the cpu is reset
+    fn reset(&mut self) {}
+
+    // called when the board is powered on, devices with undefined contents fill them from the seed
+    fn power_on(&mut self, _seed: u64) {}
+
+    // checked after every write, a device returning true stops the emulation
+    fn halt_requested(&self) -> bool {
+        false
+    }
+
+    // how many offsets the device decodes, attach rejects a window that reaches past them
+    // None for devices that answer at any offset
+    fn size(&self) -> Option<usize> {
+        None
+    }
+
+    // shown in the memory map
+    fn name(&self) -> &str {
+        let type_name = std::any::type_name::<Self>();
+        type_name.rsplit("::").next().unwrap_or(type_name)
+    }
+}
+
+// writing anything here asks the cpu to stop, e.g. at the end of a test programm
+pub struct Halt;
+
+impl BusDevice for Halt {
+    fn read(&mut self, _: u16) -> u8 {
+        0x00
+    }
+
+    fn write(&mut self, _: u16, _: u8) {}
+
+    fn peek(&self, _: u16) -> u8 {
+        0x00
+    }
+
+    fn halt_requested(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "Halt"
+    }
 }
 
-impl Device {
-    fn read(&self, addr: u16) -> u8 {
-        match self {
-            Device::Ram(ram) => ram.read(addr),
-            Device::Rom(rom) => rom.read(addr),
-            Device::Display(_) => 0x00,
+// the addresses a device answers to
+// boards often decode only some of the address lines, the mask keeps the offset bits the
+// device sees, so a 2K ram in an 8K window with mask 0x07FF shows up four times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: u16,
+    pub end: u16,
+    pub mask: u16,
+}
+
+impl Window {
+    pub fn new(start: u16, end: u16) -> Window {
+        Self {
+            start,
+            end,
+            mask: 0xFFFF,
         }
     }
 
-    fn write(&mut self, addr: u16, data: u8) {
-        match self {
-            Device::Ram(ram) => ram.write(addr, data),
-            Device::Rom(rom) => rom.write(addr, data),
-            Device::Display(display) => display.write(data),
+    pub fn mirrored(self, mask: u16) -> Window {
+        Self { mask, ..self }
+    }
+
+    // offset of addr within the window as the device sees it
+    fn offset(&self, addr: u16) -> u16 {
+        (addr - self.start) & self.mask
+    }
+
+    // the highest offset the device gets to see, either the span itself or the span with one
+    // of its bits cleared and all bits below set
+    fn last_offset(&self) -> u16 {
+        let span = self.end - self.start;
+        (0..16)
+            .filter(|bit| span & (1 << bit) != 0)
+            .map(|bit| (span & !(1 << bit) | ((1 << bit) - 1)) & self.mask)
+            .fold(span & self.mask, u16::max)
+    }
+
+    // some offsets in the window differ only in bits the mask drops
+    fn is_mirrored(&self) -> bool {
+        let dropped = !self.mask;
+        dropped != 0 && dropped & dropped.wrapping_neg() <= self.end - self.start
+    }
+}
+
+// plain (start, end) windows without mirroring
+impl From<(u16, u16)> for Window {
+    fn from((start, end): (u16, u16)) -> Window {
+        Window::new(start, end)
+    }
+}
+
+// a stretch of addresses decoded to the same device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    pub start: u16,
+    pub end: u16,
+    pub name: String,
+    // offset mask of the device if it repeats within the stretch
+    pub mirror_mask: Option<u16>,
+}
+
+// every mapped stretch of the address space in ascending order, gaps are unmapped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub entries: Vec<MapEntry>,
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut next = 0x0000_u32;
+        for entry in &self.entries {
+            if entry.start as u32 > next {
+                writeln!(f, "${next:04X}-${:04X}  unmapped", entry.start - 1)?;
+            }
+            write!(f, "${:04X}-${:04X}  {}", entry.start, entry.end, entry.name)?;
+            match entry.mirror_mask {
+                Some(mask) => writeln!(f, " mirrored, mask ${mask:04X}")?,
+                None => writeln!(f)?,
+            }
+            next = entry.end as u32 + 1;
+        }
+        if next <= 0xFFFF {
+            writeln!(f, "${next:04X}-$FFFF  unmapped")?;
         }
+        Ok(())
     }
 }
 
-type AddrRange = (u16, u16);
+struct Region {
+    start: u16,
+    end: u16,
+    index: u8,
+}
+
+// marks an address in the decoder that no device answers to
+const UNMAPPED: u8 = 0xFF;
 
 pub struct Bus {
-    connected_dev: Vec<(AddrRange, Device)>,
+    connected_dev: Vec<(Window, Box<dyn BusDevice>)>,
+    // index into connected_dev for every address, so decoding doesn't depend on the device count
+    decoder: Box<[u8]>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Bus {
     pub fn new() -> Bus {
         Self {
             connected_dev: Vec::new(),
+            decoder: vec![UNMAPPED; 0x10000].into_boxed_slice(),
         }
     }
 
-    pub fn attach(&mut self, dev: Device, addr_range: AddrRange) {
-        self.connected_dev.push((addr_range, dev));
+    // windows may not overlap, use attach_overlay for a device that covers others
+    pub fn attach(
+        &mut self,
+        dev: Box<dyn BusDevice>,
+        window: impl Into<Window>,
+    ) -> Result<(), BusError> {
+        self.map(dev, window.into(), false)
     }
 
-    pub fn write_to(&mut self, addr: u16, data: u8) {
-        for (addr_range, dev) in self.connected_dev.iter_mut() {
-            if addr >= addr_range.0 && addr <= addr_range.1 {
-                dev.write(addr, data);
-                return;
+    // the device answers in its whole window, hiding whatever was attached there before,
+    // e.g. a rom banked in over ram
+    pub fn attach_overlay(
+        &mut self,
+        dev: Box<dyn BusDevice>,
+        window: impl Into<Window>,
+    ) -> Result<(), BusError> {
+        self.map(dev, window.into(), true)
+    }
+
+    fn map(
+        &mut self,
+        dev: Box<dyn BusDevice>,
+        window: Window,
+        overlay: bool,
+    ) -> Result<(), BusError> {
+        let Window { start, end, .. } = window;
+        if start > end {
+            return Err(BusError::InvalidRange { start, end });
+        }
+        if let Some(size) = dev.size() {
+            if window.last_offset() as usize >= size {
+                return Err(BusError::DeviceTooSmall {
+                    start,
+                    end,
+                    name: dev.name().to_string(),
+                    size,
+                });
             }
         }
-        panic!("No device reads on that address");
+        if self.connected_dev.len() >= UNMAPPED as usize {
+            return Err(BusError::TooManyDevices);
+        }
+        if !overlay {
+            if let Some(addr) = (start..=end).find(|&addr| self.decoder[addr as usize] != UNMAPPED)
+            {
+                let (existing_window, existing) =
+                    &self.connected_dev[self.decoder[addr as usize] as usize];
+                return Err(BusError::Overlap {
+                    start,
+                    end,
+                    existing: existing.name().to_string(),
+                    existing_start: existing_window.start,
+                    existing_end: existing_window.end,
+                });
+            }
+        }
+
+        let index = self.connected_dev.len() as u8;
+        self.decoder[start as usize..=end as usize].fill(index);
+        self.connected_dev.push((window, dev));
+        Ok(())
     }
 
-    pub fn read_from(&self, addr: u16) -> u8 {
-        for (addr_range, dev) in self.connected_dev.iter() {
-            if addr >= addr_range.0 && addr <= addr_range.1 {
-                return dev.read(addr);
+    // the windows as the cpu sees them, overlays included
+    pub fn memory_map(&self) -> MemoryMap {
+        let mut regions: Vec<Region> = Vec::new();
+        for addr in 0..=0xFFFF_u16 {
+            let index = self.decoder[addr as usize];
+            if index == UNMAPPED {
+                continue;
             }
+            match regions.last_mut() {
+                Some(region) if region.index == index && region.end == addr - 1 => {
+                    region.end = addr;
+                }
+                _ => regions.push(Region {
+                    start: addr,
+                    end: addr,
+                    index,
+                }),
+            }
+        }
+
+        MemoryMap {
+            entries: regions
+                .into_iter()
+                .map(|region| {
+                    let (window, dev) = &self.connected_dev[region.index as usize];
+                    MapEntry {
+                        start: region.start,
+                        end: region.end,
+                        name: dev.name().to_string(),
+                        mirror_mask: window.is_mirrored().then_some(window.mask),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // the device mapped at addr and the offset of addr within its window, mirrors folded
+    fn device(&mut self, addr: u16) -> Option<(&mut Box<dyn BusDevice>, u16)> {
+        let index = self.decoder[addr as usize];
+        if index == UNMAPPED {
+            return None;
+        }
+        let (window, dev) = &mut self.connected_dev[index as usize];
+        Some((dev, window.offset(addr)))
+    }
+
+    pub fn try_write_to(&mut self, addr: u16, data: u8) -> Result<(), CpuError> {
+        let (dev, offset) = self.device(addr).ok_or(CpuError::UnmappedWrite(addr))?;
+        dev.write(offset, data);
+        if dev.halt_requested() {
+            return Err(CpuError::HaltRequested(addr));
+        }
+        Ok(())
+    }
+
+    pub fn try_read_from(&mut self, addr: u16) -> Result<u8, CpuError> {
+        let (dev, offset) = self.device(addr).ok_or(CpuError::UnmappedRead(addr))?;
+        Ok(dev.read(offset))
+    }
+
+    // side effect free read, None if nothing is mapped at addr
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        let index = self.decoder[addr as usize];
+        if index == UNMAPPED {
+            return None;
+        }
+        let (window, dev) = &self.connected_dev[index as usize];
+        Some(dev.peek(window.offset(addr)))
+    }
+
+    pub fn tick(&mut self) {
+        for (_, dev) in self.connected_dev.iter_mut() {
+            dev.tick();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for (_, dev) in self.connected_dev.iter_mut() {
+            dev.reset();
+        }
+    }
+
+    // every device gets its own seed so they don't all hold the same garbage
+    pub fn power_on(&mut self, seed: u64) {
+        let mut random = Random::new(seed);
+        for (_, dev) in self.connected_dev.iter_mut() {
+            dev.power_on(random.next_u64());
+        }
+    }
+
+    // panicking shorthands for setting up and inspecting memory
+    pub fn write_to(&mut self, addr: u16, data: u8) {
+        if let Err(err) = self.try_write_to(addr, data) {
+            panic!("{err}");
+        }
+    }
+
+    // a real read, io chips see it just like a cpu access, use peek to only look
+    pub fn read_from(&mut self, addr: u16) -> u8 {
+        self.try_read_from(addr)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bus, BusDevice, Halt, Window};
+    use crate::emulator::cpu::Cpu;
+    use crate::emulator::error::{BusError, CpuError};
+    use crate::emulator::ram::Ram;
+    use crate::emulator::rom::Rom;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    // like the status register of an io chip, reading it acknowledges the pending flag
+    struct StatusChip {
+        pending: bool,
+        ticks: Arc<AtomicU64>,
+        resets: Arc<AtomicU64>,
+    }
+
+    impl BusDevice for StatusChip {
+        fn read(&mut self, _: u16) -> u8 {
+            let status = (self.pending as u8) << 7;
+            self.pending = false;
+            status
+        }
+
+        fn write(&mut self, _: u16, _: u8) {
+            self.pending = true;
+        }
+
+        fn tick(&mut self) {
+            self.ticks.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn reset(&mut self) {
+            self.resets.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn user_device() {
+        let ticks = Arc::new(AtomicU64::new(0));
+        let resets = Arc::new(AtomicU64::new(0));
+        let chip = StatusChip {
+            pending: true,
+            ticks: ticks.clone(),
+            resets: resets.clone(),
+        };
+
+        let mut mem = Rom::from_bytes(&[0xEA; 0x10000]);
+        // LDA $D000, LDX $D000
+        mem.mem[0x0400..0x0406].copy_from_slice(&[0xAD, 0x00, 0xD0, 0xAE, 0x00, 0xD0]);
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        bus.attach_overlay(Box::new(chip), (0xD000, 0xD00F))
+            .unwrap();
+        let mut cpu = Cpu::new(bus);
+        cpu.programm_counter = 0x0400;
+
+        // peeking doesn't acknowledge anything
+        assert_eq!(cpu.peek(0xD000), 0xFF);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!((cpu.accumulator, cpu.x), (0x80, 0x00));
+        assert_eq!(ticks.load(Ordering::Relaxed), cpu.cycles);
+
+        // read_from is a real read and acknowledges as well
+        cpu.bus.write_to(0xD000, 0x00);
+        assert_eq!(cpu.bus.read_from(0xD000), 0x80);
+        assert_eq!(cpu.bus.read_from(0xD000), 0x00);
+
+        cpu.reset();
+        assert_eq!(resets.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn devices_see_offsets() {
+        let mut kernal = Rom::new(0x2000);
+        kernal.mem[0x0002] = 0x42;
+        kernal.mem[0x1FFF] = 0x99;
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Ram::new(0x0800)), (0x0000, 0x07FF))
+            .unwrap();
+        bus.attach(Box::new(kernal), (0xE000, 0xFFFF)).unwrap();
+
+        assert_eq!(bus.read_from(0xE002), 0x42);
+        assert_eq!(bus.read_from(0xFFFF), 0x99);
+        bus.write_to(0x07FF, 0x12);
+        assert_eq!(bus.read_from(0x07FF), 0x12);
+        assert_eq!(
+            bus.try_read_from(0x0800),
+            Err(CpuError::UnmappedRead(0x0800))
+        );
+    }
+
+    #[test]
+    fn small_chip_in_large_window_repeats() {
+        let mut bus = Bus::new();
+        bus.attach(
+            Box::new(Ram::new(0x0100)),
+            Window::new(0x0000, 0x03FF).mirrored(0x00FF),
+        )
+        .unwrap();
+        bus.write_to(0x0010, 0x55);
+        assert_eq!(bus.read_from(0x0310), 0x55);
+    }
+
+    #[test]
+    fn small_chip_needs_a_mirror_mask() {
+        let mut bus = Bus::new();
+        assert_eq!(
+            bus.attach(Box::new(Ram::new(0x0100)), (0x0000, 0x03FF)),
+            Err(BusError::DeviceTooSmall {
+                start: 0x0000,
+                end: 0x03FF,
+                name: "Ram".to_string(),
+                size: 0x0100,
+            })
+        );
+        // an empty chip fits nowhere
+        assert!(matches!(
+            bus.attach(Box::new(Rom::from_bytes(&[])), (0x0000, 0x0000)),
+            Err(BusError::DeviceTooSmall { size: 0, .. })
+        ));
+        // the mask decides, 0x0103 reaches offset 0x0103 of the chip
+        assert!(bus
+            .attach(
+                Box::new(Ram::new(0x0100)),
+                Window::new(0x0000, 0x03FF).mirrored(0x0103)
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn overlapping_windows_are_rejected() {
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Ram::new(0x0800)), (0x0000, 0x07FF))
+            .unwrap();
+        assert_eq!(
+            bus.attach(Box::new(Rom::new(0x0100)), (0x0700, 0x07FF)),
+            Err(BusError::Overlap {
+                start: 0x0700,
+                end: 0x07FF,
+                existing: "Ram".to_string(),
+                existing_start: 0x0000,
+                existing_end: 0x07FF,
+            })
+        );
+        assert_eq!(
+            bus.attach(Box::new(Rom::new(0x0100)), (0x1000, 0x0FFF)),
+            Err(BusError::InvalidRange {
+                start: 0x1000,
+                end: 0x0FFF
+            })
+        );
+        // the failed attempts left nothing behind
+        assert_eq!(bus.memory_map().entries.len(), 1);
+    }
+
+    #[test]
+    fn overlay_hides_what_is_below() {
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Ram::new(0x10000)), (0x0000, 0xFFFF))
+            .unwrap();
+        bus.attach_overlay(Box::new(Rom::from_bytes(&[0x42; 0x2000])), (0xA000, 0xBFFF))
+            .unwrap();
+        bus.write_to(0x9FFF, 0x01);
+        assert_eq!(bus.read_from(0x9FFF), 0x01);
+        assert_eq!(bus.read_from(0xA000), 0x42);
+
+        assert_eq!(
+            bus.memory_map().to_string(),
+            "$0000-$9FFF  Ram\n$A000-$BFFF  Rom\n$C000-$FFFF  Ram\n"
+        );
+    }
+
+    #[test]
+    fn memory_map_lists_gaps() {
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Ram::new(0x0800)), (0x0000, 0x07FF))
+            .unwrap();
+        bus.attach(Box::new(Halt), (0x0800, 0x0800)).unwrap();
+        bus.attach(Box::new(Rom::new(0x2000)), (0xE000, 0xFFFF))
+            .unwrap();
+        assert_eq!(
+            bus.memory_map().to_string(),
+            "$0000-$07FF  Ram\n$0800-$0800  Halt\n$0801-$DFFF  unmapped\n$E000-$FFFF  Rom\n"
+        );
+    }
+
+    #[test]
+    fn mirrors_share_the_device() {
+        let mut bus = Bus::new();
+        // 2K ram decoded in the lower 8K, an io chip repeating every 16 bytes across a page
+        bus.attach(
+            Box::new(Ram::new(0x0800)),
+            Window::new(0x0000, 0x1FFF).mirrored(0x07FF),
+        )
+        .unwrap();
+        bus.attach(
+            Box::new(Ram::new(0x0010)),
+            Window::new(0xD000, 0xD0FF).mirrored(0x000F),
+        )
+        .unwrap();
+
+        bus.write_to(0x0123, 0x42);
+        for mirror in [0x0923, 0x1123, 0x1923] {
+            assert_eq!(bus.read_from(mirror), 0x42);
+        }
+        bus.write_to(0xD0F3, 0x99);
+        assert_eq!(bus.read_from(0xD003), 0x99);
+
+        assert_eq!(
+            bus.memory_map().to_string(),
+            "$0000-$1FFF  Ram mirrored, mask $07FF\n$2000-$CFFF  unmapped\n\
+             $D000-$D0FF  Ram mirrored, mask $000F\n$D100-$FFFF  unmapped\n"
+        );
+    }
+
+    #[test]
+    fn mirrored_offsets_are_folded() {
+        let offsets = Arc::new(Mutex::new(Vec::new()));
+
+        struct Recorder(Arc<Mutex<Vec<u16>>>);
+        impl BusDevice for Recorder {
+            fn read(&mut self, addr: u16) -> u8 {
+                self.0.lock().unwrap().push(addr);
+                0x00
+            }
+
+            fn write(&mut self, _: u16, _: u8) {}
+        }
+
+        let mut bus = Bus::new();
+        // only A0, A1 and A8 are decoded
+        bus.attach(
+            Box::new(Recorder(offsets.clone())),
+            Window::new(0xC000, 0xCFFF).mirrored(0x0103),
+        )
+        .unwrap();
+        for addr in [0xC000, 0xC0F7, 0xC102, 0xCFFD] {
+            bus.try_read_from(addr).unwrap();
         }
-        panic!("No device writes to that address!");
+        assert_eq!(*offsets.lock().unwrap(), [0x0000, 0x0003, 0x0102, 0x0101]);
+        assert_eq!(
+            bus.memory_map().to_string(),
+            "$0000-$BFFF  unmapped\n$C000-$CFFF  Recorder mirrored, mask $0103\n$D000-$FFFF  unmapped\n"
+        );
     }
 }
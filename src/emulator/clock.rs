@@ -1,18 +1,286 @@
-use super::cpu::Cpu;
+use std::fmt;
+
+use super::cpu::{BusAccess, Cpu};
+use super::error::CpuError;
+
+// idle loops up to this many instructions are caught by trap_halts
+const IDLE_LOOP_LENGTH: u32 = 8;
+
+// why the clock stopped running the cpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    // the cpu could not carry on, this includes JAM and STP
+    Error(CpuError),
+    // a BRK at addr while break_halts is on, it is not executed
+    Break { addr: u16 },
+    // the programm loops through addr without writing or changing a register
+    Trap { addr: u16 },
+    CycleBudget,
+    InstructionBudget,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Error(err) => write!(f, "{err}"),
+            StopReason::Break { addr } => write!(f, "BRK at ${addr:04X}"),
+            StopReason::Trap { addr } => write!(f, "trapped at ${addr:04X}"),
+            StopReason::CycleBudget => write!(f, "cycle budget used up"),
+            StopReason::InstructionBudget => write!(f, "instruction budget used up"),
+        }
+    }
+}
 
 pub struct Clock {
     cpu: Cpu,
+
+    // stop at a BRK instead of running the irq handler, see Cpu::break_halts
+    pub break_halts: bool,
+    // stop in an idle loop, the programm counter comes back to the same address within a
+    // few instructions without a bus write or a register or flag change in between
+    // off by default, a JMP * waiting for an interrupt or a loop polling an io chip looks
+    // just the same
+    pub trap_halts: bool,
+    // stop once this many cycles or instructions have run since start
+    pub cycle_budget: Option<u64>,
+    pub instruction_budget: Option<u64>,
 }
 
 impl Clock {
     pub fn new(cpu: Cpu) -> Clock {
-        Self { cpu }
+        Self {
+            cpu,
+            break_halts: false,
+            trap_halts: false,
+            cycle_budget: None,
+            instruction_budget: None,
+        }
     }
 
-    pub fn start(&mut self) {
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    // runs until the cpu can't go on or one of the halt conditions is met
+    pub fn start(&mut self) -> StopReason {
         self.cpu.init_sequence();
+        self.cpu.break_halts = self.break_halts;
+        let start_cycles = self.cpu.cycles;
+        let mut instructions = 0;
+        // where a possible idle loop started and for how many instructions nothing changed
+        let mut loop_start = (self.cpu.programm_counter, registers(&self.cpu));
+        let mut idle = 0;
         loop {
-            self.cpu.pulse();
+            if self
+                .cycle_budget
+                .is_some_and(|budget| self.cpu.cycles - start_cycles >= budget)
+            {
+                return StopReason::CycleBudget;
+            }
+            if self
+                .instruction_budget
+                .is_some_and(|budget| instructions >= budget)
+            {
+                return StopReason::InstructionBudget;
+            }
+
+            let waiting = self.cpu.waiting;
+            match self.cpu.step() {
+                Ok(()) => {}
+                Err(CpuError::Break { addr }) => return StopReason::Break { addr },
+                Err(err) => return StopReason::Error(err),
+            }
+            instructions += 1;
+
+            if !self.trap_halts {
+                continue;
+            }
+            let now = (self.cpu.programm_counter, registers(&self.cpu));
+            let wrote = self
+                .cpu
+                .bus_accesses()
+                .iter()
+                .any(|access| matches!(access, BusAccess::Write(..)));
+            // a sleeping cpu stays put as well, that's not a trap
+            if wrote
+                || waiting
+                || self.cpu.waiting
+                || now.1 != loop_start.1
+                || idle == IDLE_LOOP_LENGTH
+            {
+                loop_start = now;
+                idle = 0;
+            } else if now.0 == loop_start.0 {
+                return StopReason::Trap { addr: now.0 };
+            } else {
+                idle += 1;
+            }
+        }
+    }
+}
+
+// everything but the programm counter an idle loop leaves alone
+fn registers(cpu: &Cpu) -> [u8; 5] {
+    [
+        cpu.accumulator,
+        cpu.x,
+        cpu.y,
+        cpu.stack_pointer,
+        cpu.status_flags.to_byte(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, StopReason};
+    use crate::emulator::bus::{Bus, BusDevice, Halt};
+    use crate::emulator::cpu::Cpu;
+    use crate::emulator::error::CpuError;
+    use crate::emulator::rom::Rom;
+
+    #[test]
+    fn stops_on_error() {
+        let mut mem = Rom::from_bytes(&[0xEA; 0x10000]);
+        // INX, STX $0200, JMP $0400
+        mem.mem[0x0400..0x0409].copy_from_slice(&[0xE8, 0x8E, 0x00, 0x02, 0x4C, 0x00, 0x04, 0, 0]);
+        mem.mem[0xFFFC..].copy_from_slice(&[0x00, 0x04, 0x00, 0x00]);
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        bus.attach_overlay(Box::new(Halt), (0x0200, 0x0200))
+            .unwrap();
+        let mut clock = Clock::new(Cpu::new(bus));
+        assert_eq!(
+            clock.start(),
+            StopReason::Error(CpuError::HaltRequested(0x0200))
+        );
+    }
+
+    fn cpu_with_programm(programm: &[u8]) -> Cpu {
+        let mut mem = Rom::from_bytes(&[0xEA; 0x10000]);
+        mem.mem[0x0400..0x0400 + programm.len()].copy_from_slice(programm);
+        mem.mem[0xFFFC..].copy_from_slice(&[0x00, 0x04, 0x00, 0x00]);
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        Cpu::new(bus)
+    }
+
+    fn clock_with_programm(programm: &[u8]) -> Clock {
+        Clock::new(cpu_with_programm(programm))
+    }
+
+    #[test]
+    fn stops_on_jam() {
+        let mut cpu = cpu_with_programm(&[0xEA, 0x02]);
+        cpu.illegal_opcodes = true;
+        let mut clock = Clock::new(cpu);
+        assert_eq!(
+            clock.start(),
+            StopReason::Error(CpuError::Jammed { addr: 0x0401 })
+        );
+    }
+
+    #[test]
+    fn stops_on_break() {
+        let mut clock = clock_with_programm(&[0xE8, 0x00]);
+        clock.break_halts = true;
+        assert_eq!(clock.start(), StopReason::Break { addr: 0x0401 });
+        assert_eq!(clock.cpu().x, 1);
+    }
+
+    // answers NOP when the cpu reads it but peeks as 0x00
+    struct Latch;
+
+    impl BusDevice for Latch {
+        fn read(&mut self, _: u16) -> u8 {
+            0xEA
+        }
+
+        fn write(&mut self, _: u16, _: u8) {}
+
+        fn peek(&self, _: u16) -> u8 {
+            0x00
         }
     }
+
+    #[test]
+    fn break_looks_at_the_fetched_opcode() {
+        // JMP $0200 into the latch, then NOPs
+        let mut clock = clock_with_programm(&[0x4C, 0x00, 0x02]);
+        clock
+            .cpu
+            .bus
+            .attach_overlay(Box::new(Latch), (0x0200, 0x0200))
+            .unwrap();
+        clock.break_halts = true;
+        clock.instruction_budget = Some(3);
+        assert_eq!(clock.start(), StopReason::InstructionBudget);
+        assert_eq!(clock.cpu().programm_counter, 0x0202);
+    }
+
+    fn trapping_clock(programm: &[u8]) -> Clock {
+        let mut clock = clock_with_programm(programm);
+        clock.trap_halts = true;
+        clock.instruction_budget = Some(100);
+        clock
+    }
+
+    #[test]
+    fn stops_on_trap() {
+        // JMP $0400
+        let mut clock = trapping_clock(&[0x4C, 0x00, 0x04]);
+        assert_eq!(clock.start(), StopReason::Trap { addr: 0x0400 });
+
+        // INX, BNE *
+        let mut clock = trapping_clock(&[0xE8, 0xD0, 0xFE]);
+        assert_eq!(clock.start(), StopReason::Trap { addr: 0x0401 });
+
+        // NOP, JMP $0400
+        let mut clock = trapping_clock(&[0xEA, 0x4C, 0x00, 0x04]);
+        assert_eq!(clock.start(), StopReason::Trap { addr: 0x0400 });
+
+        // LDX #$00, NOP, NOP, JMP $0403, the loop doesn't start at the first instruction
+        let mut clock = trapping_clock(&[0xA2, 0x00, 0xEA, 0xEA, 0x4C, 0x03, 0x04]);
+        assert!(matches!(clock.start(), StopReason::Trap { .. }));
+    }
+
+    #[test]
+    fn busy_loops_are_no_trap() {
+        // INC $10, JMP $0400
+        let mut clock = trapping_clock(&[0xE6, 0x10, 0x4C, 0x00, 0x04]);
+        assert_eq!(clock.start(), StopReason::InstructionBudget);
+
+        // INX, JMP $0400
+        let mut clock = trapping_clock(&[0xE8, 0x4C, 0x00, 0x04]);
+        assert_eq!(clock.start(), StopReason::InstructionBudget);
+    }
+
+    #[test]
+    fn traps_are_off_by_default() {
+        // JMP $0400, waiting for an interrupt
+        let mut clock = clock_with_programm(&[0x4C, 0x00, 0x04]);
+        clock.instruction_budget = Some(100);
+        assert_eq!(clock.start(), StopReason::InstructionBudget);
+    }
+
+    #[test]
+    fn runs_in_another_thread() {
+        // JMP $0400
+        let mut clock = trapping_clock(&[0x4C, 0x00, 0x04]);
+        let stop_reason = std::thread::spawn(move || clock.start()).join().unwrap();
+        assert_eq!(stop_reason, StopReason::Trap { addr: 0x0400 });
+    }
+
+    #[test]
+    fn stops_on_budget() {
+        // INX, JMP $0400
+        let mut clock = clock_with_programm(&[0xE8, 0x4C, 0x00, 0x04]);
+        clock.instruction_budget = Some(10);
+        assert_eq!(clock.start(), StopReason::InstructionBudget);
+        assert_eq!(clock.cpu().x, 5);
+
+        let mut clock = clock_with_programm(&[0xE8, 0x4C, 0x00, 0x04]);
+        clock.cycle_budget = Some(50);
+        assert_eq!(clock.start(), StopReason::CycleBudget);
+        assert_eq!(clock.cpu().x, 10);
+    }
 }
@@ -1,15 +1,74 @@
 use crate::emulator::bus::Bus;
+use crate::emulator::error::CpuError;
 use crate::emulator::instructionset;
+use crate::emulator::random::Random;
+use crate::emulator::status::{StatusRegister, BREAK_COMMAND_FLAG, UNUSED_FLAG};
+use crate::emulator::variant::{IoPort, Variant};
 
-#[allow(non_snake_case)]
-pub struct StatusFlag {
-    pub CARRY_FLAG: bool,
-    pub ZERO_FLAG: bool,
-    pub INTERRUPT_DISABLE_FLAG: bool,
-    pub DECIMAL_MODE_FLAG: bool,
-    pub BREAK_COMMAND_FLAG: bool,
-    pub OVERFLOW_FLAG: bool,
-    pub NEGATIVE_FLAG: bool,
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+// the stack pointer wrapped around, the stack is only 256 bytes big
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackEvent {
+    Overflow,
+    Underflow,
+}
+
+// a single cycle on the bus, every cycle of the 6502 is either a read or a write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccess {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+impl BusAccess {
+    pub fn data(self) -> u8 {
+        match self {
+            BusAccess::Read(_, data) | BusAccess::Write(_, data) => data,
+        }
+    }
+}
+
+// what the cpu does next, decided between instructions
+#[derive(Clone, Copy)]
+enum Start {
+    Instruction,
+    Nmi,
+    Irq,
+    // WAI is sleeping, one idle cycle
+    Wait,
+}
+
+// the part of the state an instruction changes apart from the bus
+#[derive(Clone, Copy)]
+struct Registers {
+    programm_counter: u16,
+    stack_pointer: u8,
+    accumulator: u8,
+    x: u8,
+    y: u8,
+    status_flags: StatusRegister,
+    cycles: u64,
+    jammed: bool,
+    waiting: bool,
+}
+
+// an instruction that is executed one bus cycle at a time
+//
+// every cycle runs the instruction again from its start, the accesses that already
+// happened are answered from the log, the next one goes to the bus and everything
+// after it is skipped and rolled back
+struct PartialStep {
+    registers: Registers,
+    start: Start,
+}
+
+struct Replay {
+    live: usize,
+    pos: usize,
+    cut_off: bool,
 }
 
 pub struct Cpu {
@@ -18,13 +77,55 @@ pub struct Cpu {
     pub accumulator: u8,
     pub x: u8,
     pub y: u8,
-    pub status_flags: StatusFlag,
+    pub status_flags: StatusRegister,
+
+    pub variant: Variant,
+    // only mapped on the 6510
+    pub io_port: IoPort,
+
+    // cycles elapsed since power on
+    pub cycles: u64,
+
+    // reproduce the nmos bug where JMP ($xxFF) reads its high byte from $xx00
+    // turn off for cmos behaviour
+    pub jmp_ind_page_wrap: bool,
+
+    // execute the stable undocumented nmos opcodes instead of panicking on them
+    pub illegal_opcodes: bool,
+    // set by a JAM (nmos) or STP (65C02) opcode, the cpu does nothing until it is reset
+    pub jammed: bool,
+    // set by WAI, the cpu sleeps until an interrupt line is asserted
+    pub waiting: bool,
+    // stop with CpuError::Break when a BRK is fetched instead of running it
+    // the programm counter stays on the BRK, interrupts are still serviced first
+    pub break_halts: bool,
+
+    // called whenever a push or pull wraps the stack pointer around
+    pub stack_hook: Option<Box<dyn FnMut(StackEvent) + Send>>,
+
+    // irq is level triggered, it is serviced as long as the line is held
+    irq_line: bool,
+    // nmi is edge triggered, only the transition to asserted is latched
+    nmi_line: bool,
+    nmi_pending: bool,
+
+    // first error raised while executing the current instruction
+    fault: Option<CpuError>,
+
+    // bus accesses of the current instruction in order
+    bus_log: Vec<BusAccess>,
+    partial_step: Option<PartialStep>,
+    replay: Option<Replay>,
 
     pub bus: Bus,
 }
 
 impl Cpu {
     pub fn new(bus: Bus) -> Cpu {
+        Self::with_variant(bus, Variant::Nmos6502)
+    }
+
+    pub fn with_variant(bus: Bus, variant: Variant) -> Cpu {
         Self {
             programm_counter: 0,
             // stack growing downwards
@@ -33,38 +134,762 @@ impl Cpu {
             accumulator: 0,
             x: 0,
             y: 0,
-            status_flags: StatusFlag {
-                CARRY_FLAG: false,
-                ZERO_FLAG: false,
-                INTERRUPT_DISABLE_FLAG: false,
-                DECIMAL_MODE_FLAG: false,
-                BREAK_COMMAND_FLAG: false,
-                OVERFLOW_FLAG: false,
-                NEGATIVE_FLAG: false,
-            },
+            status_flags: StatusRegister::new(),
+
+            variant,
+            io_port: IoPort::new(),
+
+            cycles: 0,
+
+            jmp_ind_page_wrap: true,
+
+            illegal_opcodes: false,
+            jammed: false,
+            waiting: false,
+            break_halts: false,
+
+            stack_hook: None,
+
+            irq_line: false,
+            nmi_line: false,
+            nmi_pending: false,
+
+            fault: None,
+
+            bus_log: Vec::new(),
+            partial_step: None,
+            replay: None,
 
             bus,
         }
     }
 
     pub fn init_sequence(&mut self) {
-        let programm_start_adr: u16 =
-            self.bus.read_from(0xFFFC) as u16 | (self.bus.read_from(0xFFFD) as u16) << 8;
-        self.programm_counter = programm_start_adr;
+        self.reset();
+    }
+
+    // the state of a freshly powered board: registers and ram hold whatever the seed says,
+    // only the reset sequence brings the cpu into a defined state
+    pub fn power_on(&mut self, seed: u64) {
+        let mut random = Random::new(seed);
+        self.programm_counter = random.next_u64() as u16;
+        self.stack_pointer = random.next_u8();
+        self.accumulator = random.next_u8();
+        self.x = random.next_u8();
+        self.y = random.next_u8();
+        self.status_flags =
+            StatusRegister::from_byte(random.next_u8() | UNUSED_FLAG | BREAK_COMMAND_FLAG);
+        self.bus.power_on(random.next_u64());
+        self.cycles = 0;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.partial_step = None;
+        self.replay = None;
+        self.jammed = false;
+        self.waiting = false;
+        self.nmi_pending = false;
+        self.bus_log.clear();
+
+        // the reset sequence runs through the cycles of an interrupt, but the pushes are reads
+        self.read(self.programm_counter);
+        self.read(self.programm_counter);
+        for _ in 0..3 {
+            self.read(0x0100 | self.stack_pointer as u16);
+            self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+        }
+        // A, X, Y and the other flags keep whatever they held, only the 65C02 clears decimal mode
+        self.status_flags.set_interrupt_disable(true);
+        if self.variant.is_cmos() {
+            self.status_flags.set_decimal_mode(false);
+        }
+        self.programm_counter = self.read_vector(RESET_VECTOR);
+        // nobody is there to report bus errors to
+        self.fault = None;
+    }
+
+    pub fn assert_irq(&mut self) {
+        self.irq_line = true;
+    }
+
+    pub fn release_irq(&mut self) {
+        self.irq_line = false;
+    }
+
+    pub fn assert_nmi(&mut self) {
+        if !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = true;
+    }
+
+    pub fn release_nmi(&mut self) {
+        self.nmi_line = false;
     }
 
+    // every access of the cpu goes through here, so the variant can shape the address bus
+    // each call is one cycle, bus errors don't interrupt the instruction,
+    // they are reported by step() once it is done
+    pub fn read(&mut self, addr: u16) -> u8 {
+        if let Some(data) = self.replayed() {
+            return data;
+        }
+        let addr = addr & self.variant.address_mask();
+        let data = if self.variant.has_io_port() && addr <= 0x0001 {
+            self.io_port.read(addr)
+        } else {
+            self.bus.try_read_from(addr).unwrap_or_else(|err| {
+                self.raise(err);
+                // nothing drives the data lines, they float high
+                0xFF
+            })
+        };
+        self.tick();
+        self.bus_log.push(BusAccess::Read(addr, data));
+        data
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if self.replayed().is_some() {
+            return;
+        }
+        let addr = addr & self.variant.address_mask();
+        if self.variant.has_io_port() && addr <= 0x0001 {
+            self.io_port.write(addr, data);
+        } else if let Err(err) = self.bus.try_write_to(addr, data) {
+            self.raise(err);
+        }
+        self.tick();
+        self.bus_log.push(BusAccess::Write(addr, data));
+    }
+
+    // a cycle that really happened, the devices see it as well
+    fn tick(&mut self) {
+        self.cycles += 1;
+        self.bus.tick();
+    }
+
+    // reads without spending a cycle, for disassembly and debugging
+    pub fn peek(&self, addr: u16) -> u8 {
+        let addr = addr & self.variant.address_mask();
+        if self.variant.has_io_port() && addr <= 0x0001 {
+            return self.io_port.read(addr);
+        }
+        self.bus.peek(addr).unwrap_or(0xFF)
+    }
+
+    // the bus accesses of the last instruction, or the ones done so far when cycle stepping
+    pub fn bus_accesses(&self) -> &[BusAccess] {
+        &self.bus_log
+    }
+
+    // while cycle stepping, accesses that already happened are answered from the log
+    // and the ones after the current cycle are skipped
+    // returns None for the access that really goes to the bus
+    fn replayed(&mut self) -> Option<u8> {
+        let replay = self.replay.as_mut()?;
+        let pos = replay.pos;
+        replay.pos += 1;
+        if pos < replay.live {
+            self.cycles += 1;
+            return Some(self.bus_log[pos].data());
+        }
+        if pos > replay.live {
+            replay.cut_off = true;
+            return Some(0xFF);
+        }
+        None
+    }
+
+    // whether the last access really went to the bus, side effects depend on it
+    fn last_access_live(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_none_or(|replay| replay.pos == replay.live + 1)
+    }
+
+    pub(crate) fn raise(&mut self, err: CpuError) {
+        if self.fault.is_none() {
+            self.fault = Some(err);
+        }
+    }
+
+    fn read_vector(&mut self, vector: u16) -> u16 {
+        self.read(vector) as u16 | (self.read(vector.wrapping_add(1)) as u16) << 8
+    }
+
+    // pushes the return address and the status, then jumps through the vector
+    // break_flag distinguishes a BRK from an irq in the pushed status
+    pub(crate) fn interrupt(&mut self, vector: u16, return_addr: u16, break_flag: bool) {
+        self.push((return_addr >> 8) as u8);
+        self.push(return_addr as u8);
+        self.push(self.status_flags.to_stack_byte(break_flag));
+        self.status_flags.set_interrupt_disable(true);
+        // the 65C02 also leaves decimal mode when entering a handler
+        if self.variant.is_cmos() {
+            self.status_flags.set_decimal_mode(false);
+        }
+        self.programm_counter = self.read_vector(vector);
+    }
+
+    pub fn push(&mut self, data: u8) {
+        // store first, then decrement
+        self.write(0x0100 | self.stack_pointer as u16, data);
+        if self.stack_pointer == 0x00 && self.last_access_live() {
+            self.report_stack(StackEvent::Overflow);
+        }
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+
+    pub fn pull(&mut self) -> u8 {
+        // increment first, then read
+        let underflow = self.stack_pointer == 0xFF;
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        let data = self.read(0x0100 | self.stack_pointer as u16);
+        if underflow && self.last_access_live() {
+            self.report_stack(StackEvent::Underflow);
+        }
+        data
+    }
+
+    fn report_stack(&mut self, event: StackEvent) {
+        if let Some(hook) = self.stack_hook.as_mut() {
+            hook(event);
+        }
+    }
+
+    // runs one instruction or interrupt sequence
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        // finish an instruction that was started with step_cycle
+        if self.partial_step.is_some() {
+            while !self.step_cycle()? {}
+            return Ok(());
+        }
+        if self.jammed {
+            return Err(CpuError::Jammed {
+                addr: self.programm_counter,
+            });
+        }
+        self.bus_log.clear();
+        let start = self.poll();
+        self.run(start);
+        self.finish()
+    }
+
+    // runs a single bus cycle, returns true when it completed an instruction
+    // registers only change once the instruction is complete
+    pub fn step_cycle(&mut self) -> Result<bool, CpuError> {
+        let partial_step = match self.partial_step.take() {
+            Some(partial_step) => partial_step,
+            None => {
+                if self.jammed {
+                    return Err(CpuError::Jammed {
+                        addr: self.programm_counter,
+                    });
+                }
+                self.bus_log.clear();
+                let start = self.poll();
+                PartialStep {
+                    registers: self.registers(),
+                    start,
+                }
+            }
+        };
+
+        // the instruction runs again from its start, the accesses up to now are answered from the log
+        self.set_registers(partial_step.registers);
+        self.replay = Some(Replay {
+            live: self.bus_log.len(),
+            pos: 0,
+            cut_off: false,
+        });
+        self.run(partial_step.start);
+        let cut_off = self.replay.take().is_some_and(|replay| replay.cut_off);
+
+        if cut_off {
+            self.set_registers(partial_step.registers);
+            self.cycles += self.bus_log.len() as u64;
+            self.partial_step = Some(partial_step);
+            return match self.fault.take() {
+                Some(err) => Err(err),
+                None => Ok(false),
+            };
+        }
+        self.finish().map(|_| true)
+    }
+
+    // like step() but errors are dropped, the cpu simply stays put when it jammed
     pub fn pulse(&mut self) {
-        self.exec_cycle();
+        let _ = self.step();
+    }
+
+    fn finish(&mut self) -> Result<(), CpuError> {
+        if let Some(err) = self.fault.take() {
+            return Err(err);
+        }
+        if self.jammed {
+            return Err(CpuError::Jammed {
+                addr: self.programm_counter,
+            });
+        }
+        Ok(())
     }
 
-    fn exec_cycle(&mut self) {
-        let opt_code = self.bus.read_from(self.programm_counter);
-        instructionset::exec_ins(opt_code, self);
+    // interrupts are only recognized between instructions, nmi has priority
+    fn poll(&mut self) -> Start {
+        if self.waiting {
+            // an asserted line wakes the cpu up even if the irq itself is masked
+            if !self.nmi_pending && !self.irq_line {
+                return Start::Wait;
+            }
+            self.waiting = false;
+        }
+
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            return Start::Nmi;
+        }
+        if self.irq_line && !self.status_flags.interrupt_disable() {
+            return Start::Irq;
+        }
+        Start::Instruction
+    }
+
+    fn run(&mut self, start: Start) {
+        match start {
+            Start::Instruction => {
+                let opt_code = self.read(self.programm_counter);
+                if opt_code == 0x00 && self.break_halts {
+                    self.raise(CpuError::Break {
+                        addr: self.programm_counter,
+                    });
+                    return;
+                }
+                instructionset::exec_ins(opt_code, self);
+            }
+            Start::Nmi | Start::Irq => {
+                // the opcode fetch and the following read are thrown away
+                self.read(self.programm_counter);
+                self.read(self.programm_counter);
+                let vector = match start {
+                    Start::Nmi => NMI_VECTOR,
+                    _ => IRQ_VECTOR,
+                };
+                self.interrupt(vector, self.programm_counter, false);
+            }
+            Start::Wait => self.tick(),
+        }
+    }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            programm_counter: self.programm_counter,
+            stack_pointer: self.stack_pointer,
+            accumulator: self.accumulator,
+            x: self.x,
+            y: self.y,
+            status_flags: self.status_flags,
+            cycles: self.cycles,
+            jammed: self.jammed,
+            waiting: self.waiting,
+        }
+    }
+
+    fn set_registers(&mut self, registers: Registers) {
+        self.programm_counter = registers.programm_counter;
+        self.stack_pointer = registers.stack_pointer;
+        self.accumulator = registers.accumulator;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.status_flags = registers.status_flags;
+        self.cycles = registers.cycles;
+        self.jammed = registers.jammed;
+        self.waiting = registers.waiting;
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{BusAccess, Cpu, StackEvent};
+    use crate::emulator::bus::{Bus, Halt};
+    use crate::emulator::error::CpuError;
+    use crate::emulator::ram::Ram;
+    use crate::emulator::rom::Rom;
+    use crate::emulator::variant::Variant;
+    use std::sync::{Arc, Mutex};
+
+    fn cpu_with_ram() -> Cpu {
+        let mut bus = Bus::new();
+        bus.attach(Box::new(Ram::new(0x8000)), (0x0000, 0x7FFE))
+            .unwrap();
+        Cpu::new(bus)
+    }
+
+    // NOPs everywhere, the nmi handler at 0x3000, reset at 0x4000 and irq/brk at 0x5000
+    fn cpu_with_vectors() -> Cpu {
+        let mut mem = Rom::from_bytes(&[0xEA; 0x10000]);
+        mem.mem[0x0100..0x0200].fill(0x00);
+        mem.mem[0xFFFA..].copy_from_slice(&[0x00, 0x30, 0x00, 0x40, 0x00, 0x50]);
+        mem.mem[0x3000] = 0x40;
+        mem.mem[0x5000] = 0x40;
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        let mut cpu = Cpu::new(bus);
+        cpu.programm_counter = 0x0400;
+        cpu
+    }
+
+    #[test]
+    fn reset_loads_vector() {
+        let mut cpu = cpu_with_vectors();
+        cpu.reset();
+        assert_eq!(cpu.programm_counter, 0x4000);
+        assert!(cpu.status_flags.interrupt_disable());
+        assert_eq!(cpu.stack_pointer, 0xFC);
+        assert_eq!(cpu.cycles, 7);
+    }
+
+    #[test]
+    fn irq_respects_interrupt_disable() {
+        let mut cpu = cpu_with_vectors();
+        cpu.status_flags.set_interrupt_disable(true);
+        cpu.assert_irq();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0401);
+
+        cpu.status_flags.set_interrupt_disable(false);
+        cpu.status_flags.set_carry(true);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x5000);
+        assert_eq!(cpu.cycles, 2 + 7);
+        assert!(cpu.status_flags.interrupt_disable());
+        assert_eq!(cpu.stack_pointer, 0xFC);
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x04);
+        assert_eq!(cpu.bus.read_from(0x01FE), 0x01);
+        // bit 5 set, break flag clear, interrupt disable as it was before the irq
+        assert_eq!(cpu.bus.read_from(0x01FD), 0b00100001);
+
+        // RTI restores the interrupted state
+        cpu.release_irq();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0401);
+        assert!(!cpu.status_flags.interrupt_disable());
+        assert!(cpu.status_flags.carry());
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }
+
+    #[test]
+    fn irq_is_level_triggered() {
+        let mut cpu = cpu_with_vectors();
+        cpu.assert_irq();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x5000);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0400);
+        // the line is still held, so the irq is taken again
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x5000);
+    }
+
+    #[test]
+    fn nmi_is_edge_triggered() {
+        let mut cpu = cpu_with_vectors();
+        cpu.status_flags.set_interrupt_disable(true);
+        cpu.assert_nmi();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x3000);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0400);
+        // still asserted, but there was no new edge
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0401);
+
+        cpu.release_nmi();
+        cpu.assert_nmi();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x3000);
+    }
+
+    #[test]
+    fn nmi_has_priority_over_irq() {
+        let mut cpu = cpu_with_vectors();
+        cpu.assert_irq();
+        cpu.assert_nmi();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x3000);
+    }
+
+    #[test]
+    fn break_halts_before_brk() {
+        let mut cpu = cpu_with_vectors();
+        cpu.bus.write_to(0x0400, 0x00);
+        cpu.break_halts = true;
+        assert_eq!(cpu.step(), Err(CpuError::Break { addr: 0x0400 }));
+        assert_eq!(cpu.programm_counter, 0x0400);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+
+        // a pending irq is serviced before the BRK is fetched
+        cpu.assert_irq();
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.programm_counter, 0x5000);
+    }
+
+    #[test]
+    fn brk_pushes_break_flag() {
+        let mut cpu = cpu_with_vectors();
+        cpu.bus.write_to(0x0400, 0x00);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x5000);
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x04);
+        assert_eq!(cpu.bus.read_from(0x01FE), 0x02);
+        assert_eq!(cpu.bus.read_from(0x01FD), 0b00110000);
+
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0402);
+    }
+
+    #[test]
+    fn cmos_interrupt_clears_decimal_mode() {
+        let mut cpu = cpu_with_vectors();
+        cpu.variant = Variant::Cmos65C02;
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.assert_nmi();
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x3000);
+        assert!(!cpu.status_flags.decimal_mode());
+
+        let mut cpu = cpu_with_vectors();
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.assert_nmi();
+        cpu.pulse();
+        assert!(cpu.status_flags.decimal_mode());
+    }
+
+    #[test]
+    fn mos6507_masks_the_address_bus() {
+        let mut cpu = Cpu::with_variant(Bus::new(), Variant::Mos6507);
+        cpu.bus
+            .attach(Box::new(Ram::new(0x8000)), (0x0000, 0x1FFF))
+            .unwrap();
+        cpu.write(0xF080, 0x42);
+        assert_eq!(cpu.read(0x1080), 0x42);
+        assert_eq!(cpu.read(0x3080), 0x42);
+        // the reset vector is fetched from the mirror at $1FFC
+        cpu.write(0x1FFC, 0x00);
+        cpu.write(0x1FFD, 0xF0);
+        cpu.reset();
+        assert_eq!(cpu.programm_counter, 0xF000);
+    }
+
+    #[test]
+    fn mos6510_io_port() {
+        let mut cpu = Cpu::with_variant(Bus::new(), Variant::Mos6510);
+        cpu.bus
+            .attach(Box::new(Ram::new(0x8000)), (0x0000, 0x7FFE))
+            .unwrap();
+        cpu.io_port.input = 0b00110000;
+        cpu.write(0x0000, 0b00000111);
+        cpu.write(0x0001, 0b11111111);
+        assert_eq!(cpu.read(0x0000), 0b00000111);
+        assert_eq!(cpu.read(0x0001), 0b00110111);
+        assert_eq!(cpu.io_port.pins(), 0b00110111);
+        // the ram underneath stays untouched
+        assert_eq!(cpu.bus.read_from(0x0001), 0x00);
+
+        let mut cpu = Cpu::new(Bus::new());
+        cpu.bus
+            .attach(Box::new(Ram::new(0x8000)), (0x0000, 0x7FFE))
+            .unwrap();
+        cpu.write(0x0001, 0xFF);
+        assert_eq!(cpu.bus.read_from(0x0001), 0xFF);
+    }
+
+    #[test]
+    fn unmapped_access_is_reported() {
+        let mut cpu = cpu_with_ram();
+        // LDA $9000
+        cpu.bus.write_to(0x0000, 0xAD);
+        cpu.bus.write_to(0x0002, 0x90);
+        cpu.programm_counter = 0x0000;
+        assert_eq!(cpu.step(), Err(CpuError::UnmappedRead(0x9000)));
+        assert_eq!(cpu.programm_counter, 0x0003);
+        assert_eq!(cpu.accumulator, 0xFF);
+
+        // STA $8000, afterwards the next instruction runs normally
+        cpu.bus.write_to(0x0003, 0x8D);
+        cpu.bus.write_to(0x0005, 0x80);
+        cpu.bus.write_to(0x0006, 0xEA);
+        assert_eq!(cpu.step(), Err(CpuError::UnmappedWrite(0x8000)));
+        assert_eq!(cpu.step(), Ok(()));
+    }
+
+    #[test]
+    fn device_requests_halt() {
+        let mut cpu = cpu_with_ram();
+        cpu.bus.attach(Box::new(Halt), (0xF000, 0xF000)).unwrap();
+        // STA $F000
+        cpu.bus.write_to(0x0000, 0x8D);
+        cpu.bus.write_to(0x0002, 0xF0);
+        cpu.programm_counter = 0x0000;
+        assert_eq!(cpu.step(), Err(CpuError::HaltRequested(0xF000)));
+        assert_eq!(cpu.programm_counter, 0x0003);
+    }
+
+    #[test]
+    fn stack_lives_on_page_one() {
+        let mut cpu = cpu_with_ram();
+        cpu.push(0x12);
+        cpu.push(0x34);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x12);
+        assert_eq!(cpu.bus.read_from(0x01FE), 0x34);
+        assert_eq!(cpu.bus.read_from(0x00FF), 0x00);
+
+        assert_eq!(cpu.pull(), 0x34);
+        assert_eq!(cpu.pull(), 0x12);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }
+
+    #[test]
+    fn stack_pointer_wraps() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+
+        let mut cpu = cpu_with_ram();
+        cpu.stack_hook = Some(Box::new(move |event| recorded.lock().unwrap().push(event)));
+        cpu.stack_pointer = 0x00;
+        cpu.push(0xAA);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(cpu.bus.read_from(0x0100), 0xAA);
+
+        assert_eq!(cpu.pull(), 0xAA);
+        assert_eq!(cpu.stack_pointer, 0x00);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [StackEvent::Overflow, StackEvent::Underflow]
+        );
+    }
+
+    #[test]
+    fn step_single_bus_cycles() {
+        // INC $2000
+        let mut mem = Rom::new(0x10000);
+        mem.mem[0x0400..0x0403].copy_from_slice(&[0xEE, 0x00, 0x20]);
+        mem.mem[0x2000] = 0x41;
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        let mut cpu = Cpu::new(bus);
+        cpu.programm_counter = 0x0400;
+
+        for cycle in 1..6 {
+            assert_eq!(cpu.step_cycle(), Ok(false));
+            assert_eq!(cpu.cycles, cycle);
+            assert_eq!(cpu.bus_accesses().len() as u64, cycle);
+            // nothing is visible before the instruction completes
+            assert_eq!(cpu.programm_counter, 0x0400);
+        }
+        assert_eq!(cpu.bus_accesses()[4], BusAccess::Write(0x2000, 0x41));
+        assert_eq!(cpu.bus.read_from(0x2000), 0x41);
+
+        assert_eq!(cpu.step_cycle(), Ok(true));
+        assert_eq!(cpu.cycles, 6);
+        assert_eq!(cpu.programm_counter, 0x0403);
+        assert_eq!(cpu.bus.read_from(0x2000), 0x42);
+    }
+
+    #[test]
+    fn step_finishes_partial_instruction() {
+        let mut cpu = cpu_with_vectors();
+        // LDA $0100 reads 0x00 from the stack page
+        cpu.bus.write_to(0x0400, 0xAD);
+        cpu.bus.write_to(0x0401, 0x00);
+        cpu.bus.write_to(0x0402, 0x01);
+        cpu.accumulator = 0xFF;
+        assert_eq!(cpu.step_cycle(), Ok(false));
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.accumulator, 0x00);
+        assert_eq!(cpu.programm_counter, 0x0403);
+        assert_eq!(cpu.cycles, 4);
+    }
+
+    #[test]
+    fn stack_hook_fires_once_per_access() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+
+        let mut cpu = cpu_with_vectors();
+        cpu.stack_hook = Some(Box::new(move |event| recorded.lock().unwrap().push(event)));
+        cpu.stack_pointer = 0x00;
+        cpu.bus.write_to(0x0400, 0x48);
+        while !cpu.step_cycle().unwrap() {}
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(*events.lock().unwrap(), [StackEvent::Overflow]);
+    }
+
+    #[test]
+    fn interrupt_bus_cycles() {
+        let mut cpu = cpu_with_vectors();
+        cpu.status_flags.set_interrupt_disable(false);
+        cpu.stack_pointer = 0xFF;
+        cpu.assert_irq();
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(
+            cpu.bus_accesses(),
+            [
+                BusAccess::Read(0x0400, 0xEA),
+                BusAccess::Read(0x0400, 0xEA),
+                BusAccess::Write(0x01FF, 0x04),
+                BusAccess::Write(0x01FE, 0x00),
+                BusAccess::Write(0x01FD, 0x20),
+                BusAccess::Read(0xFFFE, 0x00),
+                BusAccess::Read(0xFFFF, 0x50),
+            ]
+        );
+    }
+
+    #[test]
+    fn reset_does_not_write() {
+        let mut cpu = cpu_with_vectors();
+        cpu.accumulator = 0x12;
+        cpu.x = 0x34;
+        cpu.y = 0x56;
+        cpu.stack_pointer = 0x01;
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.reset();
+        assert_eq!(cpu.stack_pointer, 0xFE);
+        assert_eq!((cpu.accumulator, cpu.x, cpu.y), (0x12, 0x34, 0x56));
+        assert!(cpu.status_flags.decimal_mode());
+        assert_eq!(cpu.bus_accesses().len(), 7);
+        assert!(cpu
+            .bus_accesses()
+            .iter()
+            .all(|access| matches!(access, BusAccess::Read(..))));
+
+        cpu.variant = Variant::Cmos65C02;
+        cpu.reset();
+        assert!(!cpu.status_flags.decimal_mode());
+    }
+
+    #[test]
+    fn power_on_is_seeded() {
+        let power_on = |seed| {
+            let mut cpu = cpu_with_ram();
+            cpu.power_on(seed);
+            let ram: Vec<u8> = (0..0x0800).map(|addr| cpu.bus.read_from(addr)).collect();
+            (cpu.accumulator, cpu.x, cpu.y, cpu.stack_pointer, ram)
+        };
+        assert_eq!(power_on(1), power_on(1));
+        assert_ne!(power_on(1), power_on(2));
+
+        let mut cpu = cpu_with_ram();
+        cpu.power_on(1);
+        assert!(cpu.status_flags.interrupt_disable());
+        assert_eq!(cpu.cycles, 7);
+    }
+
     #[test]
     fn bitshift_u8_to_u16() {
         let little: u8 = 0b00000011;
@@ -1,9 +1,17 @@
 use std::io::{self, Write};
 
+use crate::emulator::bus::BusDevice;
+
 pub struct Display {
     message: String,
 }
 
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display {
     pub fn new() -> Display {
         Self {
@@ -26,7 +34,7 @@ impl Display {
         self.message = "".to_string();
     }
 
-    pub fn write(&mut self, data: u8) {
+    pub fn put_char(&mut self, data: u8) {
         match data {
             // the idea was to create a display thats deletable but too lazy rn
             0x80 => self.clear(),
@@ -41,3 +49,18 @@ impl Display {
         //self.println();
     }
 }
+
+// every byte written to the display is printed, where it is attached doesn't matter
+impl BusDevice for Display {
+    fn read(&mut self, _: u16) -> u8 {
+        0x00
+    }
+
+    fn write(&mut self, _: u16, data: u8) {
+        self.put_char(data);
+    }
+
+    fn peek(&self, _: u16) -> u8 {
+        0x00
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+// why the cpu could not carry on, returned by Cpu::step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    // the opcode at addr is not defined for the variant or illegal opcodes are turned off
+    InvalidOpcode { opt_code: u8, addr: u16 },
+    // a JAM or STP at addr stopped the cpu, only a reset gets it going again
+    Jammed { addr: u16 },
+    // no device is attached at the address
+    UnmappedRead(u16),
+    UnmappedWrite(u16),
+    // a device asked to stop the emulation, addr is where it is attached
+    HaltRequested(u16),
+    // a BRK was fetched at addr while Cpu::break_halts is on, it is not executed
+    Break { addr: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::InvalidOpcode { opt_code, addr } => {
+                write!(f, "invalid opcode ${opt_code:02X} at ${addr:04X}")
+            }
+            CpuError::Jammed { addr } => write!(f, "cpu jammed at ${addr:04X}"),
+            CpuError::UnmappedRead(addr) => write!(f, "no device reads from ${addr:04X}"),
+            CpuError::UnmappedWrite(addr) => write!(f, "no device writes to ${addr:04X}"),
+            CpuError::HaltRequested(addr) => write!(f, "halt requested by device at ${addr:04X}"),
+            CpuError::Break { addr } => write!(f, "BRK at ${addr:04X}"),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+// a mistake in the memory map, returned by Bus::attach
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusError {
+    // the start of the window lies behind its end
+    InvalidRange {
+        start: u16,
+        end: u16,
+    },
+    // the window overlaps one that is already attached and no overlay was asked for
+    Overlap {
+        start: u16,
+        end: u16,
+        existing: String,
+        existing_start: u16,
+        existing_end: u16,
+    },
+    // the window reaches offsets the device doesn't have, mirror it with a smaller mask
+    DeviceTooSmall {
+        start: u16,
+        end: u16,
+        name: String,
+        size: usize,
+    },
+    // the bus can't tell more devices apart
+    TooManyDevices,
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::InvalidRange { start, end } => {
+                write!(f, "invalid window ${start:04X}-${end:04X}")
+            }
+            BusError::Overlap {
+                start,
+                end,
+                existing,
+                existing_start,
+                existing_end,
+            } => write!(
+                f,
+                "window ${start:04X}-${end:04X} overlaps {existing} at ${existing_start:04X}-${existing_end:04X}"
+            ),
+            BusError::DeviceTooSmall {
+                start,
+                end,
+                name,
+                size,
+            } => write!(
+                f,
+                "window ${start:04X}-${end:04X} is larger than {name} with ${size:04X} bytes"
+            ),
+            BusError::TooManyDevices => write!(f, "too many devices on the bus"),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
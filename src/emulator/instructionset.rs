@@ -1,14 +1,14 @@
-use crate::emulator::cpu::Cpu;
-
-static CARRY_FLAG: u8 = 0b10000000;
-static ZERO_FLAG: u8 = 0b01000000;
-static INTERRUPT_DISABLE_FLAG: u8 = 0b00100000;
-static DECIMAL_MODE_FLAG: u8 = 0b00010000;
-static BREAK_COMMAND_FLAG: u8 = 0b00001000;
-static OVERFLOW_FLAG: u8 = 0b000000100;
-static NEGATIVE_FLAG: u8 = 0b00000010;
-
-enum MemMode {
+use crate::emulator::address::{resolve, Access};
+use crate::emulator::cpu::{Cpu, IRQ_VECTOR};
+use crate::emulator::error::CpuError;
+use crate::emulator::variant::Variant;
+// keeps the opcode tables readable
+use MemMode::*;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemMode {
+    IMP,
     ACC,
     IMM,
     ZPG,
@@ -21,761 +21,2918 @@ enum MemMode {
     IND,
     IDXIND,
     INDIDX,
+    // 65C02 only
+    ZPGIND,
+    ABSXIND,
+    // zero page operand followed by a branch offset, BBR/BBS
+    ZPGREL,
 }
 
-macro_rules! not_implemented {
-    () => {
-        panic!("not yet implemented");
-    };
+impl MemMode {
+    // length of an instruction using this mode, opcode included
+    pub const fn bytes(self) -> u8 {
+        match self {
+            MemMode::IMP | MemMode::ACC => 1,
+            MemMode::IMM
+            | MemMode::ZPG
+            | MemMode::ZPGX
+            | MemMode::ZPGY
+            | MemMode::REL
+            | MemMode::IDXIND
+            | MemMode::INDIDX
+            | MemMode::ZPGIND => 2,
+            MemMode::ABS
+            | MemMode::ABSX
+            | MemMode::ABSY
+            | MemMode::IND
+            | MemMode::ABSXIND
+            | MemMode::ZPGREL => 3,
+        }
+    }
 }
 
-pub fn exec_ins(opt_code: u8, cpu: &mut Cpu) {
-    match opt_code {
-        0x69 => ADC(cpu, MemMode::IMM, 2),
-        0x65 => ADC(cpu, MemMode::ZPG, 2),
-        0x75 => ADC(cpu, MemMode::ZPGX, 2),
-        0x6D => ADC(cpu, MemMode::ABS, 3),
-        0x7D => ADC(cpu, MemMode::ABSX, 3),
-        0x79 => ADC(cpu, MemMode::ABSY, 3),
-
-        0x29 => AND(cpu, MemMode::IMM, 2),
-        0x25 => AND(cpu, MemMode::ZPG, 2),
-        0x35 => AND(cpu, MemMode::ZPGX, 2),
-        0x2D => AND(cpu, MemMode::ABS, 3),
-        0x3D => AND(cpu, MemMode::ABSX, 3),
-        0x39 => AND(cpu, MemMode::ABSY, 3),
-
-        0x0A => ASL(cpu, MemMode::ACC, 1),
-        0x06 => ASL(cpu, MemMode::ZPG, 2),
-        0x16 => ASL(cpu, MemMode::ZPGX, 2),
-        0x0E => ASL(cpu, MemMode::ABS, 3),
-        0x1E => ASL(cpu, MemMode::ABSX, 3),
-
-        0x90 => BCC(cpu),
-
-        0xB0 => BCS(cpu),
-
-        0xF0 => BEQ(cpu),
-
-        0x24 => BIT(cpu, MemMode::ZPG, 2),
-        0x2C => BIT(cpu, MemMode::ABS, 3),
-
-        0x30 => BMI(cpu),
-
-        0xD0 => BNE(cpu),
-
-        0x10 => BPL(cpu),
-
-        0x00 => BRK(cpu),
-
-        0x50 => BVC(cpu),
-
-        0x70 => BVS(cpu),
-
-        0x18 => CLC(cpu),
-
-        0xD8 => CLD(cpu),
-
-        0x58 => CLI(cpu),
-
-        0xB8 => CLV(cpu),
-
-        0xC9 => CMP(cpu, MemMode::IMM, 2),
-        0xC5 => CMP(cpu, MemMode::ZPG, 2),
-        0xD5 => CMP(cpu, MemMode::ZPGX, 2),
-        0xCD => CMP(cpu, MemMode::ABS, 3),
-        0xDD => CMP(cpu, MemMode::ABSX, 3),
-        0xD9 => CMP(cpu, MemMode::ABSY, 3),
-
-        0xE0 => CPX(cpu, MemMode::IMM, 2),
-        0xE4 => CPX(cpu, MemMode::ZPG, 2),
-        0xEC => CPX(cpu, MemMode::ABS, 3),
-
-        0xC0 => CPY(cpu, MemMode::IMM, 2),
-        0xC4 => CPY(cpu, MemMode::ZPG, 2),
-        0xCC => CPY(cpu, MemMode::ABS, 3),
-
-        0xC6 => DEC(cpu, MemMode::ZPG, 2),
-        0xD6 => DEC(cpu, MemMode::ZPGX, 2),
-        0xCE => DEC(cpu, MemMode::ABS, 3),
-        0xDE => DEC(cpu, MemMode::ABSX, 3),
-
-        0xCA => DEX(cpu),
-
-        0x88 => DEY(cpu),
-
-        0x49 => EOR(cpu, MemMode::IMM, 2),
-        0x45 => EOR(cpu, MemMode::ZPG, 2),
-        0x55 => EOR(cpu, MemMode::ZPGX, 2),
-        0x4D => EOR(cpu, MemMode::ABS, 3),
-        0x5D => EOR(cpu, MemMode::ABSX, 3),
-        0x59 => EOR(cpu, MemMode::ABSY, 3),
-
-        0xE6 => INC(cpu, MemMode::ZPG, 2),
-        0xF6 => INC(cpu, MemMode::ZPGX, 2),
-        0xEE => INC(cpu, MemMode::ABS, 3),
-        0xFE => INC(cpu, MemMode::ABSX, 3),
-
-        0xE8 => INX(cpu),
-
-        0xC8 => INX(cpu),
-
-        0x4C => JMP(cpu, MemMode::ABS),
-        0x6C => JMP(cpu, MemMode::IND),
-
-        0x20 => JSR(cpu),
-
-        0xA9 => LDA(cpu, MemMode::IMM, 2),
-        0xA5 => LDA(cpu, MemMode::ZPG, 2),
-        0xB5 => LDA(cpu, MemMode::ZPGX, 2),
-        0xAD => LDA(cpu, MemMode::ABS, 3),
-        0xBD => LDA(cpu, MemMode::ABSX, 3),
-        0xB9 => LDA(cpu, MemMode::ABSY, 3),
-
-        0xA2 => LDX(cpu, MemMode::IMM, 2),
-        0xA6 => LDX(cpu, MemMode::ZPG, 2),
-        0xB6 => LDX(cpu, MemMode::ZPGY, 2),
-        0xAE => LDX(cpu, MemMode::ABS, 3),
-        0xBE => LDX(cpu, MemMode::ABSY, 3),
-
-        0xA0 => LDY(cpu, MemMode::IMM, 2),
-        0xA4 => LDY(cpu, MemMode::ZPG, 2),
-        0xB4 => LDY(cpu, MemMode::ZPGX, 2),
-        0xAC => LDY(cpu, MemMode::ABS, 3),
-        0xBC => LDY(cpu, MemMode::ABSX, 3),
-
-        0x4A => LSR(cpu, MemMode::ACC, 1),
-        0x46 => LSR(cpu, MemMode::ZPG, 2),
-        0x56 => LSR(cpu, MemMode::ZPGX, 2),
-        0x4E => LSR(cpu, MemMode::ABS, 3),
-        0x5E => LSR(cpu, MemMode::ABSX, 3),
+type Handler = fn(&mut Cpu, MemMode, u16);
+
+// everything there is to know about an opcode, shared by execution and disassembly
+#[derive(Clone, Copy)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub mem_mode: MemMode,
+    // length of the instruction including the opcode
+    pub bytes: u8,
+    pub cycles: u8,
+    // undocumented nmos opcode, only executed with Cpu::illegal_opcodes set
+    pub illegal: bool,
+    handler: Handler,
+}
 
-        0xEA => NOP(cpu),
+const fn op(mnemonic: &'static str, mem_mode: MemMode, cycles: u8, handler: Handler) -> Opcode {
+    Opcode {
+        mnemonic,
+        mem_mode,
+        bytes: mem_mode.bytes(),
+        cycles,
+        illegal: false,
+        handler,
+    }
+}
 
-        0x09 => ORA(cpu, MemMode::IMM, 2),
-        0x05 => ORA(cpu, MemMode::ZPG, 2),
-        0x15 => ORA(cpu, MemMode::ZPGX, 2),
-        0x0D => ORA(cpu, MemMode::ABS, 3),
-        0x1D => ORA(cpu, MemMode::ABSX, 3),
-        0x19 => ORA(cpu, MemMode::ABSY, 3),
+const fn illegal(
+    mnemonic: &'static str,
+    mem_mode: MemMode,
+    cycles: u8,
+    handler: Handler,
+) -> Opcode {
+    Opcode {
+        illegal: true,
+        ..op(mnemonic, mem_mode, cycles, handler)
+    }
+}
 
-        0x48 => PHA(cpu),
+pub fn opcode_table(variant: Variant) -> &'static [Opcode; 256] {
+    if variant.is_cmos() {
+        &CMOS_OPCODES
+    } else {
+        &NMOS_OPCODES
+    }
+}
 
-        0x08 => PHP(cpu),
+// every nmos opcode, page crossing and branch penalties come on top of the base cycles
+#[rustfmt::skip]
+static NMOS_OPCODES: [Opcode; 256] = [
+    op("BRK", IMP, 7, BRK), // 00
+    op("ORA", IDXIND, 6, ORA), // 01
+    illegal("JAM", IMP, 2, JAM), // 02
+    illegal("SLO", IDXIND, 8, SLO), // 03
+    illegal("NOP", ZPG, 3, NOP_READ), // 04
+    op("ORA", ZPG, 3, ORA), // 05
+    op("ASL", ZPG, 5, ASL), // 06
+    illegal("SLO", ZPG, 5, SLO), // 07
+    op("PHP", IMP, 3, PHP), // 08
+    op("ORA", IMM, 2, ORA), // 09
+    op("ASL", ACC, 2, ASL), // 0A
+    illegal("ANC", IMM, 2, ANC), // 0B
+    illegal("NOP", ABS, 4, NOP_READ), // 0C
+    op("ORA", ABS, 4, ORA), // 0D
+    op("ASL", ABS, 6, ASL), // 0E
+    illegal("SLO", ABS, 6, SLO), // 0F
+    op("BPL", REL, 2, BPL), // 10
+    op("ORA", INDIDX, 5, ORA), // 11
+    illegal("JAM", IMP, 2, JAM), // 12
+    illegal("SLO", INDIDX, 8, SLO), // 13
+    illegal("NOP", ZPGX, 4, NOP_READ), // 14
+    op("ORA", ZPGX, 4, ORA), // 15
+    op("ASL", ZPGX, 6, ASL), // 16
+    illegal("SLO", ZPGX, 6, SLO), // 17
+    op("CLC", IMP, 2, CLC), // 18
+    op("ORA", ABSY, 4, ORA), // 19
+    illegal("NOP", IMP, 2, NOP), // 1A
+    illegal("SLO", ABSY, 7, SLO), // 1B
+    illegal("NOP", ABSX, 4, NOP_READ), // 1C
+    op("ORA", ABSX, 4, ORA), // 1D
+    op("ASL", ABSX, 7, ASL), // 1E
+    illegal("SLO", ABSX, 7, SLO), // 1F
+    op("JSR", ABS, 6, JSR), // 20
+    op("AND", IDXIND, 6, AND), // 21
+    illegal("JAM", IMP, 2, JAM), // 22
+    illegal("RLA", IDXIND, 8, RLA), // 23
+    op("BIT", ZPG, 3, BIT), // 24
+    op("AND", ZPG, 3, AND), // 25
+    op("ROL", ZPG, 5, ROL), // 26
+    illegal("RLA", ZPG, 5, RLA), // 27
+    op("PLP", IMP, 4, PLP), // 28
+    op("AND", IMM, 2, AND), // 29
+    op("ROL", ACC, 2, ROL), // 2A
+    illegal("ANC", IMM, 2, ANC), // 2B
+    op("BIT", ABS, 4, BIT), // 2C
+    op("AND", ABS, 4, AND), // 2D
+    op("ROL", ABS, 6, ROL), // 2E
+    illegal("RLA", ABS, 6, RLA), // 2F
+    op("BMI", REL, 2, BMI), // 30
+    op("AND", INDIDX, 5, AND), // 31
+    illegal("JAM", IMP, 2, JAM), // 32
+    illegal("RLA", INDIDX, 8, RLA), // 33
+    illegal("NOP", ZPGX, 4, NOP_READ), // 34
+    op("AND", ZPGX, 4, AND), // 35
+    op("ROL", ZPGX, 6, ROL), // 36
+    illegal("RLA", ZPGX, 6, RLA), // 37
+    op("SEC", IMP, 2, SEC), // 38
+    op("AND", ABSY, 4, AND), // 39
+    illegal("NOP", IMP, 2, NOP), // 3A
+    illegal("RLA", ABSY, 7, RLA), // 3B
+    illegal("NOP", ABSX, 4, NOP_READ), // 3C
+    op("AND", ABSX, 4, AND), // 3D
+    op("ROL", ABSX, 7, ROL), // 3E
+    illegal("RLA", ABSX, 7, RLA), // 3F
+    op("RTI", IMP, 6, RTI), // 40
+    op("EOR", IDXIND, 6, EOR), // 41
+    illegal("JAM", IMP, 2, JAM), // 42
+    illegal("SRE", IDXIND, 8, SRE), // 43
+    illegal("NOP", ZPG, 3, NOP_READ), // 44
+    op("EOR", ZPG, 3, EOR), // 45
+    op("LSR", ZPG, 5, LSR), // 46
+    illegal("SRE", ZPG, 5, SRE), // 47
+    op("PHA", IMP, 3, PHA), // 48
+    op("EOR", IMM, 2, EOR), // 49
+    op("LSR", ACC, 2, LSR), // 4A
+    illegal("ALR", IMM, 2, ALR), // 4B
+    op("JMP", ABS, 3, JMP), // 4C
+    op("EOR", ABS, 4, EOR), // 4D
+    op("LSR", ABS, 6, LSR), // 4E
+    illegal("SRE", ABS, 6, SRE), // 4F
+    op("BVC", REL, 2, BVC), // 50
+    op("EOR", INDIDX, 5, EOR), // 51
+    illegal("JAM", IMP, 2, JAM), // 52
+    illegal("SRE", INDIDX, 8, SRE), // 53
+    illegal("NOP", ZPGX, 4, NOP_READ), // 54
+    op("EOR", ZPGX, 4, EOR), // 55
+    op("LSR", ZPGX, 6, LSR), // 56
+    illegal("SRE", ZPGX, 6, SRE), // 57
+    op("CLI", IMP, 2, CLI), // 58
+    op("EOR", ABSY, 4, EOR), // 59
+    illegal("NOP", IMP, 2, NOP), // 5A
+    illegal("SRE", ABSY, 7, SRE), // 5B
+    illegal("NOP", ABSX, 4, NOP_READ), // 5C
+    op("EOR", ABSX, 4, EOR), // 5D
+    op("LSR", ABSX, 7, LSR), // 5E
+    illegal("SRE", ABSX, 7, SRE), // 5F
+    op("RTS", IMP, 6, RTS), // 60
+    op("ADC", IDXIND, 6, ADC), // 61
+    illegal("JAM", IMP, 2, JAM), // 62
+    illegal("RRA", IDXIND, 8, RRA), // 63
+    illegal("NOP", ZPG, 3, NOP_READ), // 64
+    op("ADC", ZPG, 3, ADC), // 65
+    op("ROR", ZPG, 5, ROR), // 66
+    illegal("RRA", ZPG, 5, RRA), // 67
+    op("PLA", IMP, 4, PLA), // 68
+    op("ADC", IMM, 2, ADC), // 69
+    op("ROR", ACC, 2, ROR), // 6A
+    illegal("ARR", IMM, 2, ARR), // 6B
+    op("JMP", IND, 5, JMP), // 6C
+    op("ADC", ABS, 4, ADC), // 6D
+    op("ROR", ABS, 6, ROR), // 6E
+    illegal("RRA", ABS, 6, RRA), // 6F
+    op("BVS", REL, 2, BVS), // 70
+    op("ADC", INDIDX, 5, ADC), // 71
+    illegal("JAM", IMP, 2, JAM), // 72
+    illegal("RRA", INDIDX, 8, RRA), // 73
+    illegal("NOP", ZPGX, 4, NOP_READ), // 74
+    op("ADC", ZPGX, 4, ADC), // 75
+    op("ROR", ZPGX, 6, ROR), // 76
+    illegal("RRA", ZPGX, 6, RRA), // 77
+    op("SEI", IMP, 2, SEI), // 78
+    op("ADC", ABSY, 4, ADC), // 79
+    illegal("NOP", IMP, 2, NOP), // 7A
+    illegal("RRA", ABSY, 7, RRA), // 7B
+    illegal("NOP", ABSX, 4, NOP_READ), // 7C
+    op("ADC", ABSX, 4, ADC), // 7D
+    op("ROR", ABSX, 7, ROR), // 7E
+    illegal("RRA", ABSX, 7, RRA), // 7F
+    illegal("NOP", IMM, 2, NOP_READ), // 80
+    op("STA", IDXIND, 6, STA), // 81
+    illegal("NOP", IMM, 2, NOP_READ), // 82
+    illegal("SAX", IDXIND, 6, SAX), // 83
+    op("STY", ZPG, 3, STY), // 84
+    op("STA", ZPG, 3, STA), // 85
+    op("STX", ZPG, 3, STX), // 86
+    illegal("SAX", ZPG, 3, SAX), // 87
+    op("DEY", IMP, 2, DEY), // 88
+    illegal("NOP", IMM, 2, NOP_READ), // 89
+    op("TXA", IMP, 2, TXA), // 8A
+    illegal("XAA", IMM, 2, UNSTABLE), // 8B
+    op("STY", ABS, 4, STY), // 8C
+    op("STA", ABS, 4, STA), // 8D
+    op("STX", ABS, 4, STX), // 8E
+    illegal("SAX", ABS, 4, SAX), // 8F
+    op("BCC", REL, 2, BCC), // 90
+    op("STA", INDIDX, 6, STA), // 91
+    illegal("JAM", IMP, 2, JAM), // 92
+    illegal("SHA", INDIDX, 6, UNSTABLE), // 93
+    op("STY", ZPGX, 4, STY), // 94
+    op("STA", ZPGX, 4, STA), // 95
+    op("STX", ZPGY, 4, STX), // 96
+    illegal("SAX", ZPGY, 4, SAX), // 97
+    op("TYA", IMP, 2, TYA), // 98
+    op("STA", ABSY, 5, STA), // 99
+    op("TXS", IMP, 2, TXS), // 9A
+    illegal("TAS", ABSY, 5, UNSTABLE), // 9B
+    illegal("SHY", ABSX, 5, UNSTABLE), // 9C
+    op("STA", ABSX, 5, STA), // 9D
+    illegal("SHX", ABSY, 5, UNSTABLE), // 9E
+    illegal("SHA", ABSY, 5, UNSTABLE), // 9F
+    op("LDY", IMM, 2, LDY), // A0
+    op("LDA", IDXIND, 6, LDA), // A1
+    op("LDX", IMM, 2, LDX), // A2
+    illegal("LAX", IDXIND, 6, LAX), // A3
+    op("LDY", ZPG, 3, LDY), // A4
+    op("LDA", ZPG, 3, LDA), // A5
+    op("LDX", ZPG, 3, LDX), // A6
+    illegal("LAX", ZPG, 3, LAX), // A7
+    op("TAY", IMP, 2, TAY), // A8
+    op("LDA", IMM, 2, LDA), // A9
+    op("TAX", IMP, 2, TAX), // AA
+    illegal("LXA", IMM, 2, UNSTABLE), // AB
+    op("LDY", ABS, 4, LDY), // AC
+    op("LDA", ABS, 4, LDA), // AD
+    op("LDX", ABS, 4, LDX), // AE
+    illegal("LAX", ABS, 4, LAX), // AF
+    op("BCS", REL, 2, BCS), // B0
+    op("LDA", INDIDX, 5, LDA), // B1
+    illegal("JAM", IMP, 2, JAM), // B2
+    illegal("LAX", INDIDX, 5, LAX), // B3
+    op("LDY", ZPGX, 4, LDY), // B4
+    op("LDA", ZPGX, 4, LDA), // B5
+    op("LDX", ZPGY, 4, LDX), // B6
+    illegal("LAX", ZPGY, 4, LAX), // B7
+    op("CLV", IMP, 2, CLV), // B8
+    op("LDA", ABSY, 4, LDA), // B9
+    op("TSX", IMP, 2, TSX), // BA
+    illegal("LAS", ABSY, 4, UNSTABLE), // BB
+    op("LDY", ABSX, 4, LDY), // BC
+    op("LDA", ABSX, 4, LDA), // BD
+    op("LDX", ABSY, 4, LDX), // BE
+    illegal("LAX", ABSY, 4, LAX), // BF
+    op("CPY", IMM, 2, CPY), // C0
+    op("CMP", IDXIND, 6, CMP), // C1
+    illegal("NOP", IMM, 2, NOP_READ), // C2
+    illegal("DCP", IDXIND, 8, DCP), // C3
+    op("CPY", ZPG, 3, CPY), // C4
+    op("CMP", ZPG, 3, CMP), // C5
+    op("DEC", ZPG, 5, DEC), // C6
+    illegal("DCP", ZPG, 5, DCP), // C7
+    op("INY", IMP, 2, INY), // C8
+    op("CMP", IMM, 2, CMP), // C9
+    op("DEX", IMP, 2, DEX), // CA
+    illegal("SBX", IMM, 2, SBX), // CB
+    op("CPY", ABS, 4, CPY), // CC
+    op("CMP", ABS, 4, CMP), // CD
+    op("DEC", ABS, 6, DEC), // CE
+    illegal("DCP", ABS, 6, DCP), // CF
+    op("BNE", REL, 2, BNE), // D0
+    op("CMP", INDIDX, 5, CMP), // D1
+    illegal("JAM", IMP, 2, JAM), // D2
+    illegal("DCP", INDIDX, 8, DCP), // D3
+    illegal("NOP", ZPGX, 4, NOP_READ), // D4
+    op("CMP", ZPGX, 4, CMP), // D5
+    op("DEC", ZPGX, 6, DEC), // D6
+    illegal("DCP", ZPGX, 6, DCP), // D7
+    op("CLD", IMP, 2, CLD), // D8
+    op("CMP", ABSY, 4, CMP), // D9
+    illegal("NOP", IMP, 2, NOP), // DA
+    illegal("DCP", ABSY, 7, DCP), // DB
+    illegal("NOP", ABSX, 4, NOP_READ), // DC
+    op("CMP", ABSX, 4, CMP), // DD
+    op("DEC", ABSX, 7, DEC), // DE
+    illegal("DCP", ABSX, 7, DCP), // DF
+    op("CPX", IMM, 2, CPX), // E0
+    op("SBC", IDXIND, 6, SBC), // E1
+    illegal("NOP", IMM, 2, NOP_READ), // E2
+    illegal("ISC", IDXIND, 8, ISC), // E3
+    op("CPX", ZPG, 3, CPX), // E4
+    op("SBC", ZPG, 3, SBC), // E5
+    op("INC", ZPG, 5, INC), // E6
+    illegal("ISC", ZPG, 5, ISC), // E7
+    op("INX", IMP, 2, INX), // E8
+    op("SBC", IMM, 2, SBC), // E9
+    op("NOP", IMP, 2, NOP), // EA
+    illegal("SBC", IMM, 2, SBC), // EB
+    op("CPX", ABS, 4, CPX), // EC
+    op("SBC", ABS, 4, SBC), // ED
+    op("INC", ABS, 6, INC), // EE
+    illegal("ISC", ABS, 6, ISC), // EF
+    op("BEQ", REL, 2, BEQ), // F0
+    op("SBC", INDIDX, 5, SBC), // F1
+    illegal("JAM", IMP, 2, JAM), // F2
+    illegal("ISC", INDIDX, 8, ISC), // F3
+    illegal("NOP", ZPGX, 4, NOP_READ), // F4
+    op("SBC", ZPGX, 4, SBC), // F5
+    op("INC", ZPGX, 6, INC), // F6
+    illegal("ISC", ZPGX, 6, ISC), // F7
+    op("SED", IMP, 2, SED), // F8
+    op("SBC", ABSY, 4, SBC), // F9
+    illegal("NOP", IMP, 2, NOP), // FA
+    illegal("ISC", ABSY, 7, ISC), // FB
+    illegal("NOP", ABSX, 4, NOP_READ), // FC
+    op("SBC", ABSX, 4, SBC), // FD
+    op("INC", ABSX, 7, INC), // FE
+    illegal("ISC", ABSX, 7, ISC), // FF
+];
+
+// the 65C02 defines all 256 opcodes, the unused ones are NOPs of a fixed size
+#[rustfmt::skip]
+static CMOS_OPCODES: [Opcode; 256] = [
+    op("BRK", IMP, 7, BRK), // 00
+    op("ORA", IDXIND, 6, ORA), // 01
+    op("NOP", IMM, 2, NOP_READ), // 02
+    op("NOP", IMP, 1, NOP), // 03
+    op("TSB", ZPG, 5, TSB), // 04
+    op("ORA", ZPG, 3, ORA), // 05
+    op("ASL", ZPG, 5, ASL), // 06
+    op("RMB0", ZPG, 5, RMB::<0>), // 07
+    op("PHP", IMP, 3, PHP), // 08
+    op("ORA", IMM, 2, ORA), // 09
+    op("ASL", ACC, 2, ASL), // 0A
+    op("NOP", IMP, 1, NOP), // 0B
+    op("TSB", ABS, 6, TSB), // 0C
+    op("ORA", ABS, 4, ORA), // 0D
+    op("ASL", ABS, 6, ASL), // 0E
+    op("BBR0", ZPGREL, 5, BBR::<0>), // 0F
+    op("BPL", REL, 2, BPL), // 10
+    op("ORA", INDIDX, 5, ORA), // 11
+    op("ORA", ZPGIND, 5, ORA), // 12
+    op("NOP", IMP, 1, NOP), // 13
+    op("TRB", ZPG, 5, TRB), // 14
+    op("ORA", ZPGX, 4, ORA), // 15
+    op("ASL", ZPGX, 6, ASL), // 16
+    op("RMB1", ZPG, 5, RMB::<1>), // 17
+    op("CLC", IMP, 2, CLC), // 18
+    op("ORA", ABSY, 4, ORA), // 19
+    op("INC", ACC, 2, INC), // 1A
+    op("NOP", IMP, 1, NOP), // 1B
+    op("TRB", ABS, 6, TRB), // 1C
+    op("ORA", ABSX, 4, ORA), // 1D
+    op("ASL", ABSX, 6, ASL), // 1E
+    op("BBR1", ZPGREL, 5, BBR::<1>), // 1F
+    op("JSR", ABS, 6, JSR), // 20
+    op("AND", IDXIND, 6, AND), // 21
+    op("NOP", IMM, 2, NOP_READ), // 22
+    op("NOP", IMP, 1, NOP), // 23
+    op("BIT", ZPG, 3, BIT), // 24
+    op("AND", ZPG, 3, AND), // 25
+    op("ROL", ZPG, 5, ROL), // 26
+    op("RMB2", ZPG, 5, RMB::<2>), // 27
+    op("PLP", IMP, 4, PLP), // 28
+    op("AND", IMM, 2, AND), // 29
+    op("ROL", ACC, 2, ROL), // 2A
+    op("NOP", IMP, 1, NOP), // 2B
+    op("BIT", ABS, 4, BIT), // 2C
+    op("AND", ABS, 4, AND), // 2D
+    op("ROL", ABS, 6, ROL), // 2E
+    op("BBR2", ZPGREL, 5, BBR::<2>), // 2F
+    op("BMI", REL, 2, BMI), // 30
+    op("AND", INDIDX, 5, AND), // 31
+    op("AND", ZPGIND, 5, AND), // 32
+    op("NOP", IMP, 1, NOP), // 33
+    op("BIT", ZPGX, 4, BIT), // 34
+    op("AND", ZPGX, 4, AND), // 35
+    op("ROL", ZPGX, 6, ROL), // 36
+    op("RMB3", ZPG, 5, RMB::<3>), // 37
+    op("SEC", IMP, 2, SEC), // 38
+    op("AND", ABSY, 4, AND), // 39
+    op("DEC", ACC, 2, DEC), // 3A
+    op("NOP", IMP, 1, NOP), // 3B
+    op("BIT", ABSX, 4, BIT), // 3C
+    op("AND", ABSX, 4, AND), // 3D
+    op("ROL", ABSX, 6, ROL), // 3E
+    op("BBR3", ZPGREL, 5, BBR::<3>), // 3F
+    op("RTI", IMP, 6, RTI), // 40
+    op("EOR", IDXIND, 6, EOR), // 41
+    op("NOP", IMM, 2, NOP_READ), // 42
+    op("NOP", IMP, 1, NOP), // 43
+    op("NOP", ZPG, 3, NOP_READ), // 44
+    op("EOR", ZPG, 3, EOR), // 45
+    op("LSR", ZPG, 5, LSR), // 46
+    op("RMB4", ZPG, 5, RMB::<4>), // 47
+    op("PHA", IMP, 3, PHA), // 48
+    op("EOR", IMM, 2, EOR), // 49
+    op("LSR", ACC, 2, LSR), // 4A
+    op("NOP", IMP, 1, NOP), // 4B
+    op("JMP", ABS, 3, JMP), // 4C
+    op("EOR", ABS, 4, EOR), // 4D
+    op("LSR", ABS, 6, LSR), // 4E
+    op("BBR4", ZPGREL, 5, BBR::<4>), // 4F
+    op("BVC", REL, 2, BVC), // 50
+    op("EOR", INDIDX, 5, EOR), // 51
+    op("EOR", ZPGIND, 5, EOR), // 52
+    op("NOP", IMP, 1, NOP), // 53
+    op("NOP", ZPGX, 4, NOP_READ), // 54
+    op("EOR", ZPGX, 4, EOR), // 55
+    op("LSR", ZPGX, 6, LSR), // 56
+    op("RMB5", ZPG, 5, RMB::<5>), // 57
+    op("CLI", IMP, 2, CLI), // 58
+    op("EOR", ABSY, 4, EOR), // 59
+    op("PHY", IMP, 3, PHY), // 5A
+    op("NOP", IMP, 1, NOP), // 5B
+    op("NOP", ABS, 8, NOP_SLOW), // 5C
+    op("EOR", ABSX, 4, EOR), // 5D
+    op("LSR", ABSX, 6, LSR), // 5E
+    op("BBR5", ZPGREL, 5, BBR::<5>), // 5F
+    op("RTS", IMP, 6, RTS), // 60
+    op("ADC", IDXIND, 6, ADC), // 61
+    op("NOP", IMM, 2, NOP_READ), // 62
+    op("NOP", IMP, 1, NOP), // 63
+    op("STZ", ZPG, 3, STZ), // 64
+    op("ADC", ZPG, 3, ADC), // 65
+    op("ROR", ZPG, 5, ROR), // 66
+    op("RMB6", ZPG, 5, RMB::<6>), // 67
+    op("PLA", IMP, 4, PLA), // 68
+    op("ADC", IMM, 2, ADC), // 69
+    op("ROR", ACC, 2, ROR), // 6A
+    op("NOP", IMP, 1, NOP), // 6B
+    op("JMP", IND, 6, JMP), // 6C
+    op("ADC", ABS, 4, ADC), // 6D
+    op("ROR", ABS, 6, ROR), // 6E
+    op("BBR6", ZPGREL, 5, BBR::<6>), // 6F
+    op("BVS", REL, 2, BVS), // 70
+    op("ADC", INDIDX, 5, ADC), // 71
+    op("ADC", ZPGIND, 5, ADC), // 72
+    op("NOP", IMP, 1, NOP), // 73
+    op("STZ", ZPGX, 4, STZ), // 74
+    op("ADC", ZPGX, 4, ADC), // 75
+    op("ROR", ZPGX, 6, ROR), // 76
+    op("RMB7", ZPG, 5, RMB::<7>), // 77
+    op("SEI", IMP, 2, SEI), // 78
+    op("ADC", ABSY, 4, ADC), // 79
+    op("PLY", IMP, 4, PLY), // 7A
+    op("NOP", IMP, 1, NOP), // 7B
+    op("JMP", ABSXIND, 6, JMP), // 7C
+    op("ADC", ABSX, 4, ADC), // 7D
+    op("ROR", ABSX, 6, ROR), // 7E
+    op("BBR7", ZPGREL, 5, BBR::<7>), // 7F
+    op("BRA", REL, 2, BRA), // 80
+    op("STA", IDXIND, 6, STA), // 81
+    op("NOP", IMM, 2, NOP_READ), // 82
+    op("NOP", IMP, 1, NOP), // 83
+    op("STY", ZPG, 3, STY), // 84
+    op("STA", ZPG, 3, STA), // 85
+    op("STX", ZPG, 3, STX), // 86
+    op("SMB0", ZPG, 5, SMB::<0>), // 87
+    op("DEY", IMP, 2, DEY), // 88
+    op("BIT", IMM, 2, BIT), // 89
+    op("TXA", IMP, 2, TXA), // 8A
+    op("NOP", IMP, 1, NOP), // 8B
+    op("STY", ABS, 4, STY), // 8C
+    op("STA", ABS, 4, STA), // 8D
+    op("STX", ABS, 4, STX), // 8E
+    op("BBS0", ZPGREL, 5, BBS::<0>), // 8F
+    op("BCC", REL, 2, BCC), // 90
+    op("STA", INDIDX, 6, STA), // 91
+    op("STA", ZPGIND, 5, STA), // 92
+    op("NOP", IMP, 1, NOP), // 93
+    op("STY", ZPGX, 4, STY), // 94
+    op("STA", ZPGX, 4, STA), // 95
+    op("STX", ZPGY, 4, STX), // 96
+    op("SMB1", ZPG, 5, SMB::<1>), // 97
+    op("TYA", IMP, 2, TYA), // 98
+    op("STA", ABSY, 5, STA), // 99
+    op("TXS", IMP, 2, TXS), // 9A
+    op("NOP", IMP, 1, NOP), // 9B
+    op("STZ", ABS, 4, STZ), // 9C
+    op("STA", ABSX, 5, STA), // 9D
+    op("STZ", ABSX, 5, STZ), // 9E
+    op("BBS1", ZPGREL, 5, BBS::<1>), // 9F
+    op("LDY", IMM, 2, LDY), // A0
+    op("LDA", IDXIND, 6, LDA), // A1
+    op("LDX", IMM, 2, LDX), // A2
+    op("NOP", IMP, 1, NOP), // A3
+    op("LDY", ZPG, 3, LDY), // A4
+    op("LDA", ZPG, 3, LDA), // A5
+    op("LDX", ZPG, 3, LDX), // A6
+    op("SMB2", ZPG, 5, SMB::<2>), // A7
+    op("TAY", IMP, 2, TAY), // A8
+    op("LDA", IMM, 2, LDA), // A9
+    op("TAX", IMP, 2, TAX), // AA
+    op("NOP", IMP, 1, NOP), // AB
+    op("LDY", ABS, 4, LDY), // AC
+    op("LDA", ABS, 4, LDA), // AD
+    op("LDX", ABS, 4, LDX), // AE
+    op("BBS2", ZPGREL, 5, BBS::<2>), // AF
+    op("BCS", REL, 2, BCS), // B0
+    op("LDA", INDIDX, 5, LDA), // B1
+    op("LDA", ZPGIND, 5, LDA), // B2
+    op("NOP", IMP, 1, NOP), // B3
+    op("LDY", ZPGX, 4, LDY), // B4
+    op("LDA", ZPGX, 4, LDA), // B5
+    op("LDX", ZPGY, 4, LDX), // B6
+    op("SMB3", ZPG, 5, SMB::<3>), // B7
+    op("CLV", IMP, 2, CLV), // B8
+    op("LDA", ABSY, 4, LDA), // B9
+    op("TSX", IMP, 2, TSX), // BA
+    op("NOP", IMP, 1, NOP), // BB
+    op("LDY", ABSX, 4, LDY), // BC
+    op("LDA", ABSX, 4, LDA), // BD
+    op("LDX", ABSY, 4, LDX), // BE
+    op("BBS3", ZPGREL, 5, BBS::<3>), // BF
+    op("CPY", IMM, 2, CPY), // C0
+    op("CMP", IDXIND, 6, CMP), // C1
+    op("NOP", IMM, 2, NOP_READ), // C2
+    op("NOP", IMP, 1, NOP), // C3
+    op("CPY", ZPG, 3, CPY), // C4
+    op("CMP", ZPG, 3, CMP), // C5
+    op("DEC", ZPG, 5, DEC), // C6
+    op("SMB4", ZPG, 5, SMB::<4>), // C7
+    op("INY", IMP, 2, INY), // C8
+    op("CMP", IMM, 2, CMP), // C9
+    op("DEX", IMP, 2, DEX), // CA
+    op("WAI", IMP, 3, WAI), // CB
+    op("CPY", ABS, 4, CPY), // CC
+    op("CMP", ABS, 4, CMP), // CD
+    op("DEC", ABS, 6, DEC), // CE
+    op("BBS4", ZPGREL, 5, BBS::<4>), // CF
+    op("BNE", REL, 2, BNE), // D0
+    op("CMP", INDIDX, 5, CMP), // D1
+    op("CMP", ZPGIND, 5, CMP), // D2
+    op("NOP", IMP, 1, NOP), // D3
+    op("NOP", ZPGX, 4, NOP_READ), // D4
+    op("CMP", ZPGX, 4, CMP), // D5
+    op("DEC", ZPGX, 6, DEC), // D6
+    op("SMB5", ZPG, 5, SMB::<5>), // D7
+    op("CLD", IMP, 2, CLD), // D8
+    op("CMP", ABSY, 4, CMP), // D9
+    op("PHX", IMP, 3, PHX), // DA
+    op("STP", IMP, 3, STP), // DB
+    op("NOP", ABS, 4, NOP_READ), // DC
+    op("CMP", ABSX, 4, CMP), // DD
+    op("DEC", ABSX, 7, DEC), // DE
+    op("BBS5", ZPGREL, 5, BBS::<5>), // DF
+    op("CPX", IMM, 2, CPX), // E0
+    op("SBC", IDXIND, 6, SBC), // E1
+    op("NOP", IMM, 2, NOP_READ), // E2
+    op("NOP", IMP, 1, NOP), // E3
+    op("CPX", ZPG, 3, CPX), // E4
+    op("SBC", ZPG, 3, SBC), // E5
+    op("INC", ZPG, 5, INC), // E6
+    op("SMB6", ZPG, 5, SMB::<6>), // E7
+    op("INX", IMP, 2, INX), // E8
+    op("SBC", IMM, 2, SBC), // E9
+    op("NOP", IMP, 2, NOP), // EA
+    op("NOP", IMP, 1, NOP), // EB
+    op("CPX", ABS, 4, CPX), // EC
+    op("SBC", ABS, 4, SBC), // ED
+    op("INC", ABS, 6, INC), // EE
+    op("BBS6", ZPGREL, 5, BBS::<6>), // EF
+    op("BEQ", REL, 2, BEQ), // F0
+    op("SBC", INDIDX, 5, SBC), // F1
+    op("SBC", ZPGIND, 5, SBC), // F2
+    op("NOP", IMP, 1, NOP), // F3
+    op("NOP", ZPGX, 4, NOP_READ), // F4
+    op("SBC", ZPGX, 4, SBC), // F5
+    op("INC", ZPGX, 6, INC), // F6
+    op("SMB7", ZPG, 5, SMB::<7>), // F7
+    op("SED", IMP, 2, SED), // F8
+    op("SBC", ABSY, 4, SBC), // F9
+    op("PLX", IMP, 4, PLX), // FA
+    op("NOP", IMP, 1, NOP), // FB
+    op("NOP", ABS, 4, NOP_READ), // FC
+    op("SBC", ABSX, 4, SBC), // FD
+    op("INC", ABSX, 7, INC), // FE
+    op("BBS7", ZPGREL, 5, BBS::<7>), // FF
+];
 
-        0x68 => PLA(cpu),
+pub fn exec_ins(opt_code: u8, cpu: &mut Cpu) {
+    let opcode = &opcode_table(cpu.variant)[opt_code as usize];
+    if opcode.illegal && !cpu.illegal_opcodes {
+        cpu.raise(CpuError::InvalidOpcode {
+            opt_code,
+            addr: cpu.programm_counter,
+        });
+        return;
+    }
 
-        0x28 => PLP(cpu),
+    // single byte instructions read the following byte and throw it away,
+    // only the one cycle NOPs of the 65C02 skip this
+    if matches!(opcode.mem_mode, MemMode::IMP | MemMode::ACC) && opcode.cycles > 1 {
+        cpu.read(cpu.programm_counter.wrapping_add(1));
+    }
+    (opcode.handler)(cpu, opcode.mem_mode, opcode.bytes as u16);
+}
 
-        0x2A => ROL(cpu, MemMode::ACC, 1),
-        0x26 => ROL(cpu, MemMode::ZPG, 2),
-        0x36 => ROL(cpu, MemMode::ZPGX, 2),
-        0x2E => ROL(cpu, MemMode::ABS, 3),
-        0x3E => ROL(cpu, MemMode::ABSX, 3),
+// renders the instruction at addr in the usual assembler syntax, returns it with its length
+pub fn disassemble(cpu: &Cpu, addr: u16) -> (String, u8) {
+    let opcode = &opcode_table(cpu.variant)[cpu.peek(addr) as usize];
+    let byte = |offset: u16| cpu.peek(addr.wrapping_add(offset));
+    let word = || byte(1) as u16 | (byte(2) as u16) << 8;
+    let rel = |offset: u16, from: u16| {
+        addr.wrapping_add(from)
+            .wrapping_add(byte(offset) as i8 as u16)
+    };
 
-        0x6A => ROR(cpu, MemMode::ACC, 1),
-        0x66 => ROR(cpu, MemMode::ZPG, 2),
-        0x76 => ROR(cpu, MemMode::ZPGX, 2),
-        0x6E => ROR(cpu, MemMode::ABS, 3),
-        0x7E => ROR(cpu, MemMode::ABSX, 3),
+    let operand = match opcode.mem_mode {
+        MemMode::IMP => String::new(),
+        MemMode::ACC => "A".to_string(),
+        MemMode::IMM => format!("#${:02X}", byte(1)),
+        MemMode::ZPG => format!("${:02X}", byte(1)),
+        MemMode::ZPGX => format!("${:02X},X", byte(1)),
+        MemMode::ZPGY => format!("${:02X},Y", byte(1)),
+        MemMode::REL => format!("${:04X}", rel(1, 2)),
+        MemMode::ABS => format!("${:04X}", word()),
+        MemMode::ABSX => format!("${:04X},X", word()),
+        MemMode::ABSY => format!("${:04X},Y", word()),
+        MemMode::IND => format!("(${:04X})", word()),
+        MemMode::IDXIND => format!("(${:02X},X)", byte(1)),
+        MemMode::INDIDX => format!("(${:02X}),Y", byte(1)),
+        MemMode::ZPGIND => format!("(${:02X})", byte(1)),
+        MemMode::ABSXIND => format!("(${:04X},X)", word()),
+        MemMode::ZPGREL => format!("${:02X},${:04X}", byte(1), rel(2, 3)),
+    };
 
-        0x40 => RTI(cpu),
+    let text = if operand.is_empty() {
+        opcode.mnemonic.to_string()
+    } else {
+        format!("{} {}", opcode.mnemonic, operand)
+    };
+    (text, opcode.bytes)
+}
 
-        0x60 => RTS(cpu),
+// operand of a read instruction
+fn read_value(cpu: &mut Cpu, mem_mode: MemMode) -> u8 {
+    let addr = resolve(cpu, mem_mode, Access::Read).addr;
+    cpu.read(addr)
+}
 
-        0xE9 => SBC(cpu, MemMode::IMM, 2),
-        0xE5 => SBC(cpu, MemMode::ZPG, 2),
-        0xF5 => SBC(cpu, MemMode::ZPGX, 2),
-        0xED => SBC(cpu, MemMode::ABS, 3),
-        0xFD => SBC(cpu, MemMode::ABSX, 3),
-        0xF9 => SBC(cpu, MemMode::ABSY, 3),
+// operand of a read-modify-write instruction, returns (val, addr, is_acc)
+fn read_modify(cpu: &mut Cpu, mem_mode: MemMode, access: Access) -> (u8, u16, bool) {
+    if mem_mode == MemMode::ACC {
+        return (cpu.accumulator, 0, true);
+    }
+    let addr = resolve(cpu, mem_mode, access).addr;
+    (cpu.read(addr), addr, false)
+}
 
-        0x38 => SEC(cpu),
+// the nmos 6502 writes the unmodified value back while it computes the result,
+// the 65C02 reads it a second time instead
+fn write_modify(cpu: &mut Cpu, addr: u16, is_acc: bool, val: u8, modified_val: u8) {
+    if is_acc {
+        cpu.accumulator = modified_val;
+        return;
+    }
+    if cpu.variant.is_cmos() {
+        cpu.read(addr);
+    } else {
+        cpu.write(addr, val);
+    }
+    cpu.write(addr, modified_val);
+}
 
-        0xF8 => SED(cpu),
+// the cpu reads the top of the stack while it increments the stack pointer
+fn dummy_stack_read(cpu: &mut Cpu) {
+    cpu.read(0x0100 | cpu.stack_pointer as u16);
+}
 
-        0x78 => SEI(cpu),
+fn set_zero_negative(cpu: &mut Cpu, val: u8) {
+    cpu.status_flags.set_zero(val == 0);
+    cpu.status_flags.set_negative(val & 0b10000000 != 0);
+}
 
-        0x85 => STA(cpu, MemMode::ZPG, 2),
-        0x95 => STA(cpu, MemMode::ZPGX, 2),
-        0x8D => STA(cpu, MemMode::ABS, 3),
-        0x9D => STA(cpu, MemMode::ABSX, 3),
-        0x99 => STA(cpu, MemMode::ABSY, 3),
+fn compare(cpu: &mut Cpu, reg: u8, val: u8) {
+    cpu.status_flags.set_carry(reg >= val);
+    set_zero_negative(cpu, reg.wrapping_sub(val));
+}
 
-        0x86 => STX(cpu, MemMode::ZPG, 2),
-        0x96 => STX(cpu, MemMode::ZPGY, 2),
-        0x8E => STX(cpu, MemMode::ABS, 3),
+fn ADC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let to_add = read_value(cpu, mem_mode);
 
-        0x84 => STY(cpu, MemMode::ZPG, 2),
-        0x94 => STY(cpu, MemMode::ZPGX, 2),
-        0x8C => STY(cpu, MemMode::ABS, 3),
+    add(cpu, to_add, bytes);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
 
-        0xAA => TAX(cpu),
+// bytes is the length of the instruction doing the addition
+fn add(cpu: &mut Cpu, to_add: u8, bytes: u16) {
+    if cpu.status_flags.decimal_mode() && cpu.variant.has_decimal_mode() {
+        // the 65C02 spends an extra cycle fixing up the flags
+        decimal_cycle(cpu, bytes);
+        add_decimal(cpu, to_add);
+    } else {
+        add_binary(cpu, to_add);
+    }
+}
 
-        0xA8 => TAY(cpu),
+fn decimal_cycle(cpu: &mut Cpu, bytes: u16) {
+    // which address is read here isn't documented, the next opcode is a safe guess
+    if cpu.variant.is_cmos() {
+        cpu.read(cpu.programm_counter.wrapping_add(bytes));
+    }
+}
 
-        0xBA => TSX(cpu),
+fn add_binary(cpu: &mut Cpu, to_add: u8) {
+    let acc = cpu.accumulator;
+    let sum = acc as u16 + to_add as u16 + cpu.status_flags.carry() as u16;
+    let result = sum as u8;
+
+    cpu.status_flags.set_carry(sum > 0xFF);
+    // overflow if both operands have the same sign and the result has a different one
+    cpu.status_flags
+        .set_overflow((!(acc ^ to_add) & (acc ^ result)) & 0b10000000 != 0);
+    set_zero_negative(cpu, result);
+    cpu.accumulator = result;
+}
 
-        0x8A => TXA(cpu),
+fn add_decimal(cpu: &mut Cpu, to_add: u8) {
+    // see "Decimal Mode" by Bruce Clark (6502.org), Appendix A
+    // this reproduces the nmos results for invalid bcd inputs as well
+    let acc = cpu.accumulator;
+    let carry = cpu.status_flags.carry() as u16;
 
-        0x9A => TXS(cpu),
+    let mut low = (acc & 0x0F) as u16 + (to_add & 0x0F) as u16 + carry;
+    if low >= 0x0A {
+        low = ((low + 0x06) & 0x0F) + 0x10;
+    }
+    let mut sum = (acc & 0xF0) as u16 + (to_add & 0xF0) as u16 + low;
 
-        0x98 => TYA(cpu),
+    // the nmos 6502 takes Z from the binary sum, N and V from the sum before the high nibble is adjusted
+    cpu.status_flags
+        .set_zero((acc as u16 + to_add as u16 + carry) as u8 == 0);
+    cpu.status_flags.set_negative(sum & 0b10000000 != 0);
+    cpu.status_flags
+        .set_overflow((!(acc ^ to_add) & (acc ^ sum as u8)) & 0b10000000 != 0);
 
-        _ => panic!("Opt code {opt_code:x} doesn't exist or is not implemented!"),
+    if sum >= 0xA0 {
+        sum += 0x60;
     }
-}
+    cpu.status_flags.set_carry(sum > 0xFF);
+    cpu.accumulator = sum as u8;
 
-fn get_value(cpu: &Cpu, mem_mode: MemMode) -> (u8, u16, bool) {
-    // returns (val, addr, is_acc)
-    // val is the deref of addr
-    match mem_mode {
-        MemMode::ACC => (cpu.accumulator, 0, true),
-        MemMode::IMM => (cpu.bus.read_from(cpu.programm_counter + 1), 0, false),
-        MemMode::ZPG => {
-            let zpg_addr = cpu.bus.read_from(cpu.programm_counter + 1);
-            (cpu.bus.read_from(zpg_addr as u16), zpg_addr as u16, false)
-        }
-        MemMode::ZPGX => {
-            let zpg_x_addr = cpu.bus.read_from(cpu.programm_counter + 1) + cpu.x;
-            // potential wrap around
-            let zpg_x_addr_w = zpg_x_addr as u16 % 256;
-            (cpu.bus.read_from(zpg_x_addr_w as u16), zpg_x_addr_w, false)
-        }
-        MemMode::ZPGY => {
-            let zpg_y_addr = cpu.bus.read_from(cpu.programm_counter + 1) + cpu.y;
-            // potential wrap around
-            let zpg_y_addr_w = zpg_y_addr as u16 % 256;
-            (cpu.bus.read_from(zpg_y_addr_w as u16), zpg_y_addr_w, false)
-        }
-        MemMode::REL => {
-            not_implemented!();
-        }
-        MemMode::ABS => {
-            let abs_addr = cpu.bus.read_from(cpu.programm_counter + 1) as u16
-                | (cpu.bus.read_from(cpu.programm_counter + 2) as u16) << 8;
-            (cpu.bus.read_from(abs_addr), abs_addr, false)
-        }
-        MemMode::ABSX => {
-            let abs_addr = cpu.bus.read_from(cpu.programm_counter + 1) as u16
-                | (cpu.bus.read_from(cpu.programm_counter + 2) as u16) << 8;
-            let abs_addr_x = abs_addr + cpu.x as u16;
-            (cpu.bus.read_from(abs_addr_x), abs_addr_x, false)
-        }
-        MemMode::ABSY => {
-            let abs_addr = cpu.bus.read_from(cpu.programm_counter + 1) as u16
-                | (cpu.bus.read_from(cpu.programm_counter + 2) as u16) << 8;
-            let abs_addr_y = abs_addr + cpu.y as u16;
-            (cpu.bus.read_from(abs_addr_y), abs_addr_y, false)
-        }
-        MemMode::IND => {
-            not_implemented!();
-        }
-        MemMode::IDXIND => {
-            not_implemented!();
-        }
-        MemMode::INDIDX => {
-            not_implemented!();
-        }
+    // the 65C02 takes N and Z from the decimal result
+    if cpu.variant.is_cmos() {
+        set_zero_negative(cpu, cpu.accumulator);
     }
 }
 
-fn ADC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u8) {
-    let (to_add, _, _) = get_value(cpu, mem_mode);
+fn AND(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let to_and = read_value(cpu, mem_mode);
 
-    cpu.accumulator = cpu.accumulator + to_add;
-    cpu.status_flags.ZERO_FLAG = cpu.accumulator == 0;
-    cpu.programm_counter += bytes as u16;
-}
+    cpu.accumulator &= to_and;
+    set_zero_negative(cpu, cpu.accumulator);
 
-fn AND(cpu: &mut Cpu, mem_mode: MemMode, bytes: u8) {
-    let (to_and, _, _) = get_value(cpu, mem_mode);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
 
-    cpu.accumulator = cpu.accumulator & to_and;
-    cpu.status_flags.ZERO_FLAG = cpu.accumulator == 0;
+fn ASL(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Modify);
+    cpu.status_flags.set_carry(val & 0b10000000 != 0);
+    let shifted_val = val << 1;
+    set_zero_negative(cpu, shifted_val);
+    write_modify(cpu, addr, is_acc, val, shifted_val);
 
-    cpu.programm_counter += bytes as u16;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn ASL(cpu: &mut Cpu, mem_mode: MemMode, bytes: u8) {
-    let (val, addr, is_acc) = get_value(cpu, mem_mode);
-    let val = (val as u16) << 1;
-    cpu.status_flags.CARRY_FLAG = val & 0b00010000 > 0;
-    if is_acc {
-        cpu.accumulator = val as u8;
-    } else {
-        cpu.bus.write_to(addr, val as u8);
+fn branch(cpu: &mut Cpu, condition: bool) {
+    let rel_addr = resolve(cpu, MemMode::REL, Access::Read).addr;
+    let next_ins = cpu.programm_counter.wrapping_add(2);
+    if !condition {
+        cpu.programm_counter = next_ins;
+        return;
     }
 
-    cpu.programm_counter += bytes as u16;
+    take_branch(cpu, next_ins, rel_addr);
+    cpu.programm_counter = rel_addr;
 }
 
-fn BCC(cpu: &mut Cpu) {
-    let is_carry_clear = !cpu.status_flags.CARRY_FLAG;
-    if is_carry_clear {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
+// a taken branch costs one cycle, another one if it lands on a different page
+// both read from the instruction stream before the programm counter is fixed up
+fn take_branch(cpu: &mut Cpu, next_ins: u16, rel_addr: u16) {
+    cpu.read(next_ins);
+    if next_ins & 0xFF00 != rel_addr & 0xFF00 {
+        cpu.read((next_ins & 0xFF00) | (rel_addr & 0x00FF));
     }
 }
 
-fn BCS(cpu: &mut Cpu) {
-    let is_carry_set = cpu.status_flags.CARRY_FLAG;
-    if is_carry_set {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BCC(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, !cpu.status_flags.carry());
 }
 
-fn BEQ(cpu: &mut Cpu) {
-    let is_zero_set = cpu.status_flags.ZERO_FLAG;
-    if is_zero_set {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16 + 2;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BCS(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, cpu.status_flags.carry());
 }
 
-fn BIT(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+fn BEQ(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, cpu.status_flags.zero());
+}
 
-    cpu.status_flags.ZERO_FLAG = val & 0b11111111 == 0;
+fn BIT(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // BIT #imm of the 65C02 only affects the zero flag
+    let is_imm = matches!(mem_mode, MemMode::IMM);
+    let val = read_value(cpu, mem_mode);
+
+    cpu.status_flags.set_zero(cpu.accumulator & val == 0);
+    if !is_imm {
+        // bit 6 and 7 of the operand are copied regardless of the accumulator
+        cpu.status_flags.set_overflow(val & 0b01000000 != 0);
+        cpu.status_flags.set_negative(val & 0b10000000 != 0);
+    }
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn BMI(cpu: &mut Cpu) {
-    let is_negative_set = cpu.status_flags.NEGATIVE_FLAG;
-    if is_negative_set {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BMI(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, cpu.status_flags.negative());
 }
 
-fn BNE(cpu: &mut Cpu) {
-    let is_zero_clear = !cpu.status_flags.ZERO_FLAG;
-    if is_zero_clear {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BNE(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, !cpu.status_flags.zero());
 }
 
-fn BPL(cpu: &mut Cpu) {
-    let is_negative_clear = !cpu.status_flags.NEGATIVE_FLAG;
-    if is_negative_clear {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BPL(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, !cpu.status_flags.negative());
 }
 
-fn BRK(cpu: &mut Cpu) {
-    not_implemented!();
+fn BRK(cpu: &mut Cpu, _: MemMode, _: u16) {
+    // BRK skips a padding byte, the return address is PC + 2
+    let return_addr = cpu.programm_counter.wrapping_add(2);
+    cpu.interrupt(IRQ_VECTOR, return_addr, true);
 }
 
-fn BVC(cpu: &mut Cpu) {
-    let is_overflow_clear = !cpu.status_flags.OVERFLOW_FLAG;
-    if is_overflow_clear {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BVC(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, !cpu.status_flags.overflow());
 }
 
-fn BVS(cpu: &mut Cpu) {
-    let is_overflow_set = cpu.status_flags.OVERFLOW_FLAG;
-    if is_overflow_set {
-        let rel = cpu.bus.read_from(cpu.programm_counter + 1);
-        cpu.programm_counter = cpu.programm_counter + rel as u16;
-    } else {
-        cpu.programm_counter += 2;
-    }
+fn BVS(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, cpu.status_flags.overflow());
 }
 
-fn CLC(cpu: &mut Cpu) {
-    cpu.status_flags.CARRY_FLAG = false;
-    cpu.programm_counter += 1;
+fn CLC(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_carry(false);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn CLD(cpu: &mut Cpu) {
-    cpu.status_flags.DECIMAL_MODE_FLAG = false;
-    cpu.programm_counter += 1;
+fn CLD(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_decimal_mode(false);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn CLI(cpu: &mut Cpu) {
-    cpu.status_flags.INTERRUPT_DISABLE_FLAG = false;
-    cpu.programm_counter += 1;
+fn CLI(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_interrupt_disable(false);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn CLV(cpu: &mut Cpu) {
-    cpu.status_flags.OVERFLOW_FLAG = false;
-    cpu.programm_counter += 1;
+fn CLV(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_overflow(false);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn CMP(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+    let val = read_value(cpu, mem_mode);
 
-    cpu.status_flags.ZERO_FLAG = cpu.accumulator == val;
+    compare(cpu, cpu.accumulator, val);
 
-    cpu.status_flags.OVERFLOW_FLAG = cpu.accumulator >= val;
-
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn CPX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
-
-    cpu.status_flags.ZERO_FLAG = cpu.x == val;
+    let val = read_value(cpu, mem_mode);
 
-    cpu.status_flags.OVERFLOW_FLAG = cpu.x >= val;
+    compare(cpu, cpu.x, val);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn CPY(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+    let val = read_value(cpu, mem_mode);
 
-    cpu.status_flags.ZERO_FLAG = cpu.y == val;
+    compare(cpu, cpu.y, val);
 
-    cpu.status_flags.OVERFLOW_FLAG = cpu.y >= val;
-
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn DEC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, addr, _) = get_value(cpu, mem_mode);
+    // unlike the shifts, the 65C02 always fixes up the address for INC/DEC abs,X
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Write);
 
-    let val_dec = val - 1;
-    cpu.status_flags.ZERO_FLAG = val_dec == 0;
-    cpu.bus.write_to(addr, val_dec);
-    cpu.programm_counter += bytes;
+    let val_dec = val.wrapping_sub(1);
+    set_zero_negative(cpu, val_dec);
+    write_modify(cpu, addr, is_acc, val, val_dec);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn DEX(cpu: &mut Cpu) {
-    cpu.x -= 1;
+fn DEX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.x = cpu.x.wrapping_sub(1);
 
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn DEY(cpu: &mut Cpu) {
-    cpu.y -= 1;
+fn DEY(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.y = cpu.y.wrapping_sub(1);
 
-    cpu.status_flags.ZERO_FLAG = cpu.y == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.y);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn EOR(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
-    cpu.accumulator = cpu.accumulator ^ val;
+    let val = read_value(cpu, mem_mode);
+    cpu.accumulator ^= val;
 
-    cpu.status_flags.ZERO_FLAG = cpu.accumulator == 0;
+    set_zero_negative(cpu, cpu.accumulator);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn INC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, addr, _) = get_value(cpu, mem_mode);
-    let val_inc = val + 1;
+    // unlike the shifts, the 65C02 always fixes up the address for INC/DEC abs,X
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Write);
+    let val_inc = val.wrapping_add(1);
 
-    cpu.status_flags.ZERO_FLAG = val_inc == 0;
+    set_zero_negative(cpu, val_inc);
 
-    cpu.bus.write_to(addr, val_inc);
+    write_modify(cpu, addr, is_acc, val, val_inc);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn INX(cpu: &mut Cpu) {
-    cpu.x += 1;
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+fn INX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.x = cpu.x.wrapping_add(1);
+    set_zero_negative(cpu, cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn INY(cpu: &mut Cpu) {
-    cpu.y += 1;
-    cpu.status_flags.ZERO_FLAG = cpu.y == 0;
-    cpu.programm_counter += 1;
+fn INY(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.y = cpu.y.wrapping_add(1);
+    set_zero_negative(cpu, cpu.y);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn JMP(cpu: &mut Cpu, mem_mode: MemMode) {
-    let (_, addr, _) = get_value(cpu, mem_mode);
+fn JMP(cpu: &mut Cpu, mem_mode: MemMode, _: u16) {
+    let addr = resolve(cpu, mem_mode, Access::Read).addr;
     cpu.programm_counter = addr;
 }
 
-fn JSR(cpu: &mut Cpu) {
-    let (_, jump_addr, _) = get_value(cpu, MemMode::ABS);
-    // save return adresse to stack
-    // little endian
-    cpu.stack_pointer -= 1;
-    cpu.bus.write_to(
-        (cpu.stack_pointer) as u16,
-        (cpu.programm_counter + 4 >> 8) as u8,
-    );
-    cpu.stack_pointer -= 1;
-    cpu.bus
-        .write_to((cpu.stack_pointer) as u16, (cpu.programm_counter + 3) as u8);
-    cpu.programm_counter = jump_addr;
+fn JSR(cpu: &mut Cpu, _: MemMode, _: u16) {
+    // the high byte of the target is only fetched after the return address is pushed
+    let jump_addr_low = cpu.read(cpu.programm_counter.wrapping_add(1));
+    dummy_stack_read(cpu);
+    // the return address pushed is the last byte of the JSR instruction
+    // high byte first so it ends up little endian in memory
+    let return_addr = cpu.programm_counter.wrapping_add(2);
+    cpu.push((return_addr >> 8) as u8);
+    cpu.push(return_addr as u8);
+    let jump_addr_high = cpu.read(return_addr);
+    cpu.programm_counter = jump_addr_low as u16 | (jump_addr_high as u16) << 8;
 }
 
 fn LDA(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+    let val = read_value(cpu, mem_mode);
     cpu.accumulator = val;
 
-    cpu.status_flags.ZERO_FLAG = val == 0;
-    cpu.programm_counter += bytes;
+    set_zero_negative(cpu, val);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn LDX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+    let val = read_value(cpu, mem_mode);
     cpu.x = val;
 
-    cpu.status_flags.ZERO_FLAG = val == 0;
-    cpu.programm_counter += bytes;
+    set_zero_negative(cpu, val);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn LDY(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
+    let val = read_value(cpu, mem_mode);
     cpu.y = val;
 
-    cpu.status_flags.ZERO_FLAG = val == 0;
-    cpu.programm_counter += bytes;
+    set_zero_negative(cpu, val);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn LSR(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, addr, is_acc) = get_value(cpu, mem_mode);
-    cpu.status_flags.CARRY_FLAG = val & 0b00000001 != 0;
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Modify);
+    cpu.status_flags.set_carry(val & 0b00000001 != 0);
 
     let shifted_val = val >> 1;
-    cpu.status_flags.ZERO_FLAG = shifted_val == 0;
+    set_zero_negative(cpu, shifted_val);
 
-    if is_acc {
-        cpu.accumulator = shifted_val;
-    } else {
-        cpu.bus.write_to(addr, shifted_val);
-    }
+    write_modify(cpu, addr, is_acc, val, shifted_val);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn NOP(cpu: &mut Cpu) {
-    cpu.programm_counter += 1;
+fn NOP(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn ORA(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
-    cpu.accumulator = cpu.accumulator | val;
-    cpu.status_flags.ZERO_FLAG = val == 0;
-    cpu.programm_counter += bytes;
+    let val = read_value(cpu, mem_mode);
+    cpu.accumulator |= val;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn PHA(cpu: &mut Cpu) {
-    cpu.stack_pointer -= 1;
-    cpu.bus.write_to(cpu.stack_pointer as u16, cpu.accumulator);
+fn PHA(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.push(cpu.accumulator);
 
-    cpu.programm_counter += 1;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn PHP(cpu: &mut Cpu) {
-    cpu.stack_pointer -= 1;
-    let mut status_flags: u8 = 0;
-    status_flags |= (cpu.status_flags.CARRY_FLAG as u8) << 7;
-    status_flags |= (cpu.status_flags.ZERO_FLAG as u8) << 6;
-    status_flags |= (cpu.status_flags.INTERRUPT_DISABLE_FLAG as u8) << 5;
-    status_flags |= (cpu.status_flags.DECIMAL_MODE_FLAG as u8) << 4;
-    status_flags |= (cpu.status_flags.BREAK_COMMAND_FLAG as u8) << 3;
-    status_flags |= (cpu.status_flags.OVERFLOW_FLAG as u8) << 2;
-    status_flags |= (cpu.status_flags.NEGATIVE_FLAG as u8) << 1;
-    status_flags |= false as u8;
+fn PHP(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    // PHP always pushes the break flag set
+    cpu.push(cpu.status_flags.to_stack_byte(true));
 
-    cpu.bus.write_to(cpu.stack_pointer as u16, status_flags);
-
-    cpu.programm_counter += 1;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn PLA(cpu: &mut Cpu) {
-    cpu.accumulator = cpu.bus.read_from(cpu.stack_pointer as u16);
-    cpu.stack_pointer += 1;
-    cpu.programm_counter += 1;
+fn PLA(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    dummy_stack_read(cpu);
+    cpu.accumulator = cpu.pull();
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn PLP(cpu: &mut Cpu) {
-    let status_flags = cpu.bus.read_from(cpu.stack_pointer as u16);
-
-    cpu.status_flags.CARRY_FLAG = ((status_flags >> 7) & 1) != 0;
-    cpu.status_flags.ZERO_FLAG = ((status_flags >> 6) & 1) != 0;
-    cpu.status_flags.INTERRUPT_DISABLE_FLAG = ((status_flags >> 5) & 1) != 0;
-    cpu.status_flags.DECIMAL_MODE_FLAG = ((status_flags >> 4) & 1) != 0;
-    cpu.status_flags.BREAK_COMMAND_FLAG = ((status_flags >> 3) & 1) != 0;
-    cpu.status_flags.OVERFLOW_FLAG = ((status_flags >> 2) & 1) != 0;
-    cpu.status_flags.NEGATIVE_FLAG = ((status_flags >> 1) & 1) != 0;
-    cpu.stack_pointer += 1;
-    cpu.programm_counter += 1;
+fn PLP(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    dummy_stack_read(cpu);
+    let status_flags = cpu.pull();
+    cpu.status_flags.set_from_stack_byte(status_flags);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn ROL(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, addr, is_acc) = get_value(cpu, mem_mode);
-    let was_carry_set = cpu.status_flags.CARRY_FLAG;
-    cpu.status_flags.CARRY_FLAG = val & 0b10000000 != 0;
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Modify);
+    let was_carry_set = cpu.status_flags.carry();
+    cpu.status_flags.set_carry(val & 0b10000000 != 0);
 
     let mut val_shifted = val << 1;
     if was_carry_set {
-        val_shifted += 1;
+        val_shifted |= 0b00000001;
     }
+    set_zero_negative(cpu, val_shifted);
 
-    if is_acc {
-        cpu.accumulator = val_shifted;
-    } else {
-        cpu.bus.write_to(addr, val_shifted);
-    }
+    write_modify(cpu, addr, is_acc, val, val_shifted);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn ROR(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, addr, is_acc) = get_value(cpu, mem_mode);
-    let was_carry_set = cpu.status_flags.CARRY_FLAG;
-    cpu.status_flags.CARRY_FLAG = val & 0b00000001 != 0;
+    let (val, addr, is_acc) = read_modify(cpu, mem_mode, Access::Modify);
+    let was_carry_set = cpu.status_flags.carry();
+    cpu.status_flags.set_carry(val & 0b00000001 != 0);
 
     let mut val_shifted = val >> 1;
     if was_carry_set {
         val_shifted |= 0b10000000;
     }
+    set_zero_negative(cpu, val_shifted);
 
-    if is_acc {
-        cpu.accumulator = val_shifted;
-    } else {
-        cpu.bus.write_to(addr, val_shifted);
-    }
+    write_modify(cpu, addr, is_acc, val, val_shifted);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn RTI(cpu: &mut Cpu) {
-    let status_flags = cpu.bus.read_from(cpu.stack_pointer as u16);
-    let return_addr: u16 = cpu.bus.read_from((cpu.stack_pointer + 1) as u16) as u16
-        | (cpu.bus.read_from((cpu.stack_pointer + 2) as u16) as u16) << 8;
+fn RTI(cpu: &mut Cpu, _: MemMode, _: u16) {
+    dummy_stack_read(cpu);
+    let status_flags = cpu.pull();
+    let return_addr: u16 = cpu.pull() as u16 | (cpu.pull() as u16) << 8;
 
-    cpu.stack_pointer += 3;
-
-    cpu.status_flags.CARRY_FLAG = ((status_flags >> 7) & 1) != 0;
-    cpu.status_flags.ZERO_FLAG = ((status_flags >> 6) & 1) != 0;
-    cpu.status_flags.INTERRUPT_DISABLE_FLAG = ((status_flags >> 5) & 1) != 0;
-    cpu.status_flags.DECIMAL_MODE_FLAG = ((status_flags >> 4) & 1) != 0;
-    cpu.status_flags.BREAK_COMMAND_FLAG = ((status_flags >> 3) & 1) != 0;
-    cpu.status_flags.OVERFLOW_FLAG = ((status_flags >> 2) & 1) != 0;
-    cpu.status_flags.NEGATIVE_FLAG = ((status_flags >> 1) & 1) != 0;
+    cpu.status_flags.set_from_stack_byte(status_flags);
     cpu.programm_counter = return_addr;
 }
 
-fn RTS(cpu: &mut Cpu) {
-    let return_addr: u16 = cpu.bus.read_from(cpu.stack_pointer as u16) as u16
-        | (cpu.bus.read_from((cpu.stack_pointer + 1) as u16) as u16) << 8;
-
-    cpu.stack_pointer += 2;
+fn RTS(cpu: &mut Cpu, _: MemMode, _: u16) {
+    dummy_stack_read(cpu);
+    let return_addr: u16 = cpu.pull() as u16 | (cpu.pull() as u16) << 8;
 
-    cpu.programm_counter = return_addr;
+    // JSR pushed the address of its own last byte, it is read once more while incrementing
+    cpu.read(return_addr);
+    cpu.programm_counter = return_addr.wrapping_add(1);
 }
 
 fn SBC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (val, _, _) = get_value(cpu, mem_mode);
-    cpu.accumulator -= val;
+    let val = read_value(cpu, mem_mode);
+
+    subtract(cpu, val, bytes);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
 
-    cpu.status_flags.ZERO_FLAG = cpu.accumulator == 0;
+fn subtract(cpu: &mut Cpu, to_sub: u8, bytes: u16) {
+    if cpu.status_flags.decimal_mode() && cpu.variant.has_decimal_mode() {
+        decimal_cycle(cpu, bytes);
+        sub_decimal(cpu, to_sub);
+    } else {
+        // a - b - !c == a + !b + c
+        add_binary(cpu, !to_sub);
+    }
+}
+
+fn sub_decimal(cpu: &mut Cpu, to_sub: u8) {
+    // see "Decimal Mode" by Bruce Clark (6502.org), Appendix A
+    let acc = cpu.accumulator;
+    let borrow = !cpu.status_flags.carry() as i16;
+
+    let mut low = (acc & 0x0F) as i16 - (to_sub & 0x0F) as i16 - borrow;
+    let diff = if cpu.variant.is_cmos() {
+        let mut diff = acc as i16 - to_sub as i16 - borrow;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        if low < 0 {
+            diff -= 0x06;
+        }
+        diff
+    } else {
+        if low < 0 {
+            low = ((low - 0x06) & 0x0F) - 0x10;
+        }
+        let mut diff = (acc & 0xF0) as i16 - (to_sub & 0xF0) as i16 + low;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        diff
+    };
 
-    cpu.programm_counter += bytes;
+    // C and V always follow the binary subtraction, on the nmos 6502 N and Z as well
+    add_binary(cpu, !to_sub);
+    cpu.accumulator = diff as u8;
+    if cpu.variant.is_cmos() {
+        set_zero_negative(cpu, cpu.accumulator);
+    }
 }
 
-fn SEC(cpu: &mut Cpu) {
-    cpu.status_flags.CARRY_FLAG = true;
-    cpu.programm_counter += 1;
+fn SEC(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_carry(true);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn SED(cpu: &mut Cpu) {
-    cpu.status_flags.DECIMAL_MODE_FLAG = true;
-    cpu.programm_counter += 1;
+fn SED(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_decimal_mode(true);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn SEI(cpu: &mut Cpu) {
-    cpu.status_flags.INTERRUPT_DISABLE_FLAG = true;
-    cpu.programm_counter += 1;
+fn SEI(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.status_flags.set_interrupt_disable(true);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn STA(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (_, addr, _) = get_value(cpu, mem_mode);
+    let addr = resolve(cpu, mem_mode, Access::Write).addr;
 
-    cpu.bus.write_to(addr, cpu.accumulator);
+    cpu.write(addr, cpu.accumulator);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn STX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (_, addr, _) = get_value(cpu, mem_mode);
+    let addr = resolve(cpu, mem_mode, Access::Write).addr;
 
-    cpu.bus.write_to(addr, cpu.x);
+    cpu.write(addr, cpu.x);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
 fn STY(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
-    let (_, addr, _) = get_value(cpu, mem_mode);
+    let addr = resolve(cpu, mem_mode, Access::Write).addr;
 
-    cpu.bus.write_to(addr, cpu.y);
+    cpu.write(addr, cpu.y);
 
-    cpu.programm_counter += bytes;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TAX(cpu: &mut Cpu) {
+fn TAX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
     cpu.x = cpu.accumulator;
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TAY(cpu: &mut Cpu) {
+fn TAY(cpu: &mut Cpu, _: MemMode, bytes: u16) {
     cpu.y = cpu.accumulator;
-    cpu.status_flags.ZERO_FLAG = cpu.y == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.y);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TSX(cpu: &mut Cpu) {
+fn TSX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
     cpu.x = cpu.stack_pointer;
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TXA(cpu: &mut Cpu) {
+fn TXA(cpu: &mut Cpu, _: MemMode, bytes: u16) {
     cpu.accumulator = cpu.x;
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TXS(cpu: &mut Cpu) {
+fn TXS(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    // the only transfer that leaves the flags alone
     cpu.stack_pointer = cpu.x;
-    cpu.status_flags.ZERO_FLAG = cpu.x == 0;
-    cpu.programm_counter += 1;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
 }
 
-fn TYA(cpu: &mut Cpu) {
+fn TYA(cpu: &mut Cpu, _: MemMode, bytes: u16) {
     cpu.accumulator = cpu.y;
-    cpu.status_flags.ZERO_FLAG = cpu.y == 0;
-    cpu.programm_counter += 1;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+// 65C02 instructions, only reachable with Variant::Cmos65C02
+
+fn BRA(cpu: &mut Cpu, _: MemMode, _: u16) {
+    branch(cpu, true);
+}
+
+fn PHX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.push(cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn PHY(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    cpu.push(cpu.y);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn PLX(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    dummy_stack_read(cpu);
+    cpu.x = cpu.pull();
+    set_zero_negative(cpu, cpu.x);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn PLY(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    dummy_stack_read(cpu);
+    cpu.y = cpu.pull();
+    set_zero_negative(cpu, cpu.y);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn STZ(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let addr = resolve(cpu, mem_mode, Access::Write).addr;
+
+    cpu.write(addr, 0x00);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn TRB(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+
+    cpu.status_flags.set_zero(cpu.accumulator & val == 0);
+    write_modify(cpu, addr, false, val, val & !cpu.accumulator);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn TSB(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+
+    cpu.status_flags.set_zero(cpu.accumulator & val == 0);
+    write_modify(cpu, addr, false, val, val | cpu.accumulator);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn RMB<const BIT: u8>(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    write_modify(cpu, addr, false, val, val & !(1 << BIT));
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn SMB<const BIT: u8>(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    write_modify(cpu, addr, false, val, val | 1 << BIT);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn BBR<const BIT: u8>(cpu: &mut Cpu, mem_mode: MemMode, _: u16) {
+    bit_branch(cpu, mem_mode, BIT, false);
+}
+
+fn BBS<const BIT: u8>(cpu: &mut Cpu, mem_mode: MemMode, _: u16) {
+    bit_branch(cpu, mem_mode, BIT, true);
+}
+
+// BBR/BBS test a bit of a zero page byte, the signed offset follows in the third byte
+fn bit_branch(cpu: &mut Cpu, mem_mode: MemMode, bit: u8, branch_if_set: bool) {
+    let addr = resolve(cpu, mem_mode, Access::Read).addr;
+    let val = cpu.read(addr);
+    cpu.read(addr);
+    let offset = cpu.read(cpu.programm_counter.wrapping_add(2)) as i8;
+    let next_ins = cpu.programm_counter.wrapping_add(3);
+
+    if (val >> bit & 1 != 0) != branch_if_set {
+        cpu.programm_counter = next_ins;
+        return;
+    }
+
+    let rel_addr = next_ins.wrapping_add(offset as u16);
+    take_branch(cpu, next_ins, rel_addr);
+    cpu.programm_counter = rel_addr;
+}
+
+fn WAI(cpu: &mut Cpu, _: MemMode, bytes: u16) {
+    // sleeps until the next irq or nmi
+    cpu.read(cpu.programm_counter.wrapping_add(1));
+    cpu.waiting = true;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn STP(cpu: &mut Cpu, _: MemMode, _: u16) {
+    // stops the clock until the next reset, just like a JAM on the nmos 6502
+    cpu.read(cpu.programm_counter.wrapping_add(1));
+    cpu.jammed = true;
+}
+
+// undocumented instructions, only reachable with Cpu::illegal_opcodes set
+
+fn SLO(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // ASL + ORA
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    cpu.status_flags.set_carry(val & 0b10000000 != 0);
+    let shifted_val = val << 1;
+    write_modify(cpu, addr, false, val, shifted_val);
+
+    cpu.accumulator |= shifted_val;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn RLA(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // ROL + AND
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    let was_carry_set = cpu.status_flags.carry();
+    cpu.status_flags.set_carry(val & 0b10000000 != 0);
+    let shifted_val = val << 1 | was_carry_set as u8;
+    write_modify(cpu, addr, false, val, shifted_val);
+
+    cpu.accumulator &= shifted_val;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn SRE(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // LSR + EOR
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    cpu.status_flags.set_carry(val & 0b00000001 != 0);
+    let shifted_val = val >> 1;
+    write_modify(cpu, addr, false, val, shifted_val);
+
+    cpu.accumulator ^= shifted_val;
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn RRA(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // ROR + ADC, the carry out of the rotation feeds the addition
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    let was_carry_set = cpu.status_flags.carry();
+    cpu.status_flags.set_carry(val & 0b00000001 != 0);
+    let shifted_val = val >> 1 | (was_carry_set as u8) << 7;
+    write_modify(cpu, addr, false, val, shifted_val);
+
+    add(cpu, shifted_val, bytes);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn SAX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let addr = resolve(cpu, mem_mode, Access::Write).addr;
+
+    cpu.write(addr, cpu.accumulator & cpu.x);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn LAX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    let val = read_value(cpu, mem_mode);
+    cpu.accumulator = val;
+    cpu.x = val;
+
+    set_zero_negative(cpu, val);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn DCP(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // DEC + CMP
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    let val_dec = val.wrapping_sub(1);
+    write_modify(cpu, addr, false, val, val_dec);
+
+    compare(cpu, cpu.accumulator, val_dec);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn ISC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // INC + SBC
+    let (val, addr, _) = read_modify(cpu, mem_mode, Access::Modify);
+    let val_inc = val.wrapping_add(1);
+    write_modify(cpu, addr, false, val, val_inc);
+
+    subtract(cpu, val_inc, bytes);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn ANC(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // AND, then bit 7 is copied into carry as if an ASL followed
+    let val = read_value(cpu, mem_mode);
+    cpu.accumulator &= val;
+
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.status_flags
+        .set_carry(cpu.accumulator & 0b10000000 != 0);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn ALR(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // AND + LSR A
+    let val = read_value(cpu, mem_mode);
+    let anded = cpu.accumulator & val;
+    cpu.status_flags.set_carry(anded & 0b00000001 != 0);
+    cpu.accumulator = anded >> 1;
+
+    set_zero_negative(cpu, cpu.accumulator);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn ARR(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // AND + ROR A, but C and V come out of the adder
+    let val = read_value(cpu, mem_mode);
+    let anded = cpu.accumulator & val;
+    let was_carry_set = cpu.status_flags.carry();
+    let mut result = anded >> 1 | (was_carry_set as u8) << 7;
+
+    if !cpu.status_flags.decimal_mode() || !cpu.variant.has_decimal_mode() {
+        set_zero_negative(cpu, result);
+        cpu.status_flags.set_carry(result & 0b01000000 != 0);
+        cpu.status_flags
+            .set_overflow(((result >> 6) ^ (result >> 5)) & 1 != 0);
+    } else {
+        // N and Z come from the rotated value, then each nibble gets a bcd fixup
+        cpu.status_flags.set_negative(was_carry_set);
+        cpu.status_flags.set_zero(result == 0);
+        cpu.status_flags
+            .set_overflow((anded ^ result) & 0b01000000 != 0);
+        if (anded & 0x0F) + (anded & 0x01) > 0x05 {
+            result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+        }
+        let high_fixup = (anded & 0xF0) as u16 + (anded & 0x10) as u16 > 0x50;
+        if high_fixup {
+            result = result.wrapping_add(0x60);
+        }
+        cpu.status_flags.set_carry(high_fixup);
+    }
+
+    cpu.accumulator = result;
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn SBX(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // X = (A & X) - imm, flags like CMP, never decimal
+    let val = read_value(cpu, mem_mode);
+    let anded = cpu.accumulator & cpu.x;
+    compare(cpu, anded, val);
+    cpu.x = anded.wrapping_sub(val);
+
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn NOP_READ(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // reads its operand like any other instruction and throws it away
+    read_value(cpu, mem_mode);
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn NOP_SLOW(cpu: &mut Cpu, mem_mode: MemMode, bytes: u16) {
+    // 0x5C of the 65C02 keeps the bus busy for another four cycles after reading its operand,
+    // the addresses of those reads are not documented
+    let addr = resolve(cpu, mem_mode, Access::Read).addr;
+    for _ in 0..5 {
+        cpu.read(addr);
+    }
+    cpu.programm_counter = cpu.programm_counter.wrapping_add(bytes);
+}
+
+fn UNSTABLE(cpu: &mut Cpu, _: MemMode, _: u16) {
+    // too dependent on the individual chip to be emulated
+    let opt_code = cpu.peek(cpu.programm_counter);
+    cpu.raise(CpuError::InvalidOpcode {
+        opt_code,
+        addr: cpu.programm_counter,
+    });
+}
+
+fn JAM(cpu: &mut Cpu, _: MemMode, _: u16) {
+    // the cpu locks up until it is reset, the programm counter stays on the opcode
+    cpu.jammed = true;
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{disassemble, exec_ins, opcode_table, MemMode};
+    use crate::emulator::bus::Bus;
+    use crate::emulator::cpu::{BusAccess, Cpu};
+    use crate::emulator::error::CpuError;
+    use crate::emulator::rom::Rom;
+    use crate::emulator::variant::Variant;
+
+    // loads the programm at 0x0400 and backs the whole address space with writable memory
+    fn cpu_with_programm(programm: &[u8]) -> Cpu {
+        let mut mem = Rom::new(0x10000);
+        mem.mem[0x0400..0x0400 + programm.len()].copy_from_slice(programm);
+        let mut bus = Bus::new();
+        bus.attach(Box::new(mem), (0x0000, 0xFFFF)).unwrap();
+        let mut cpu = Cpu::new(bus);
+        cpu.programm_counter = 0x0400;
+        cpu
+    }
+
+    fn step(cpu: &mut Cpu) {
+        let opt_code = cpu.read(cpu.programm_counter);
+        exec_ins(opt_code, cpu);
+    }
+
+    #[test]
+    fn lda_idxind() {
+        let mut cpu = cpu_with_programm(&[0xA1, 0x20]);
+        cpu.x = 0x04;
+        cpu.bus.write_to(0x24, 0x74);
+        cpu.bus.write_to(0x25, 0x20);
+        cpu.bus.write_to(0x2074, 0x42);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x42);
+        assert_eq!(cpu.programm_counter, 0x0402);
+    }
+
+    #[test]
+    fn idxind_pointer_wraps_in_zero_page() {
+        let mut cpu = cpu_with_programm(&[0xA1, 0xFE]);
+        cpu.x = 0x01;
+        cpu.bus.write_to(0xFF, 0x34);
+        cpu.bus.write_to(0x00, 0x12);
+        cpu.bus.write_to(0x1234, 0x99);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x99);
+
+        // the index addition itself wraps as well
+        let mut cpu = cpu_with_programm(&[0xA1, 0x80]);
+        cpu.x = 0x90;
+        cpu.bus.write_to(0x10, 0x00);
+        cpu.bus.write_to(0x11, 0x30);
+        cpu.bus.write_to(0x3000, 0x55);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x55);
+    }
+
+    #[test]
+    fn lda_indidx() {
+        let mut cpu = cpu_with_programm(&[0xB1, 0x86]);
+        cpu.y = 0x10;
+        cpu.bus.write_to(0x86, 0x28);
+        cpu.bus.write_to(0x87, 0x40);
+        cpu.bus.write_to(0x4038, 0x07);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x07);
+        assert_eq!(cpu.programm_counter, 0x0402);
+    }
+
+    #[test]
+    fn indidx_pointer_wraps_in_zero_page() {
+        let mut cpu = cpu_with_programm(&[0xB1, 0xFF]);
+        cpu.y = 0x01;
+        cpu.bus.write_to(0xFF, 0x00);
+        cpu.bus.write_to(0x00, 0x50);
+        cpu.bus.write_to(0x5001, 0x66);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x66);
+    }
+
+    #[test]
+    fn indidx_y_addition_wraps_address_space() {
+        let mut cpu = cpu_with_programm(&[0x91, 0x10]);
+        cpu.accumulator = 0xAB;
+        cpu.y = 0x20;
+        cpu.bus.write_to(0x10, 0xF0);
+        cpu.bus.write_to(0x11, 0xFF);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x0010), 0xAB);
+    }
+
+    #[test]
+    fn jmp_ind() {
+        let mut cpu = cpu_with_programm(&[0x6C, 0x20, 0x01]);
+        cpu.bus.write_to(0x0120, 0xFC);
+        cpu.bus.write_to(0x0121, 0xBA);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0xBAFC);
+    }
+
+    #[test]
+    fn jmp_ind_nmos_page_wrap() {
+        let mut cpu = cpu_with_programm(&[0x6C, 0xFF, 0x30]);
+        cpu.bus.write_to(0x30FF, 0x80);
+        cpu.bus.write_to(0x3000, 0x50);
+        cpu.bus.write_to(0x3100, 0x40);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x5080);
+    }
+
+    #[test]
+    fn jmp_ind_cmos_no_page_wrap() {
+        let mut cpu = cpu_with_programm(&[0x6C, 0xFF, 0x30]);
+        cpu.jmp_ind_page_wrap = false;
+        cpu.bus.write_to(0x30FF, 0x80);
+        cpu.bus.write_to(0x3000, 0x50);
+        cpu.bus.write_to(0x3100, 0x40);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x4080);
+    }
+
+    fn adc_decimal(acc: u8, val: u8, carry: bool) -> Cpu {
+        let mut cpu = cpu_with_programm(&[0x69, val]);
+        cpu.accumulator = acc;
+        cpu.status_flags.set_carry(carry);
+        cpu.status_flags.set_decimal_mode(true);
+        step(&mut cpu);
+        cpu
+    }
+
+    fn sbc_decimal(acc: u8, val: u8, carry: bool) -> Cpu {
+        let mut cpu = cpu_with_programm(&[0xE9, val]);
+        cpu.accumulator = acc;
+        cpu.status_flags.set_carry(carry);
+        cpu.status_flags.set_decimal_mode(true);
+        step(&mut cpu);
+        cpu
+    }
+
+    #[test]
+    fn adc_decimal_carry() {
+        let cpu = adc_decimal(0x09, 0x01, false);
+        assert_eq!(cpu.accumulator, 0x10);
+        assert!(!cpu.status_flags.carry());
+
+        let cpu = adc_decimal(0x58, 0x46, true);
+        assert_eq!(cpu.accumulator, 0x05);
+        assert!(cpu.status_flags.carry());
+
+        let cpu = adc_decimal(0x12, 0x34, false);
+        assert_eq!(cpu.accumulator, 0x46);
+        assert!(!cpu.status_flags.carry());
+    }
+
+    #[test]
+    fn adc_decimal_nmos_flags() {
+        // result is 00 but Z comes from the binary sum and N from the unadjusted high nibble
+        let cpu = adc_decimal(0x99, 0x01, false);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(cpu.status_flags.carry());
+        assert!(!cpu.status_flags.zero());
+        assert!(cpu.status_flags.negative());
+        assert!(!cpu.status_flags.overflow());
+
+        let cpu = adc_decimal(0x79, 0x00, true);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert!(cpu.status_flags.negative());
+        assert!(cpu.status_flags.overflow());
+    }
+
+    #[test]
+    fn adc_decimal_invalid_bcd() {
+        let cpu = adc_decimal(0x1A, 0x00, false);
+        assert_eq!(cpu.accumulator, 0x20);
+
+        let cpu = adc_decimal(0x0F, 0x0F, false);
+        assert_eq!(cpu.accumulator, 0x14);
+        assert!(!cpu.status_flags.carry());
+
+        let cpu = adc_decimal(0xFF, 0xFF, true);
+        assert_eq!(cpu.accumulator, 0x55);
+        assert!(cpu.status_flags.carry());
+    }
+
+    #[test]
+    fn sbc_decimal_borrow() {
+        let cpu = sbc_decimal(0x46, 0x12, true);
+        assert_eq!(cpu.accumulator, 0x34);
+        assert!(cpu.status_flags.carry());
+
+        let cpu = sbc_decimal(0x40, 0x13, true);
+        assert_eq!(cpu.accumulator, 0x27);
+        assert!(cpu.status_flags.carry());
+
+        let cpu = sbc_decimal(0x32, 0x02, false);
+        assert_eq!(cpu.accumulator, 0x29);
+        assert!(cpu.status_flags.carry());
+
+        let cpu = sbc_decimal(0x00, 0x01, true);
+        assert_eq!(cpu.accumulator, 0x99);
+        assert!(!cpu.status_flags.carry());
+        // flags follow the binary subtraction 0x00 - 0x01 = 0xFF
+        assert!(cpu.status_flags.negative());
+        assert!(!cpu.status_flags.zero());
+    }
+
+    #[test]
+    fn sbc_decimal_invalid_bcd() {
+        let cpu = sbc_decimal(0x20, 0x0A, true);
+        assert_eq!(cpu.accumulator, 0x10);
+    }
+
+    #[test]
+    fn sta_idxind() {
+        let mut cpu = cpu_with_programm(&[0x81, 0x40]);
+        cpu.accumulator = 0x5A;
+        cpu.x = 0x02;
+        cpu.bus.write_to(0x42, 0x00);
+        cpu.bus.write_to(0x43, 0x02);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x0200), 0x5A);
+    }
+
+    // (negative, overflow, zero, carry)
+    fn nvzc(cpu: &Cpu) -> (bool, bool, bool, bool) {
+        (
+            cpu.status_flags.negative(),
+            cpu.status_flags.overflow(),
+            cpu.status_flags.zero(),
+            cpu.status_flags.carry(),
+        )
+    }
+
+    fn run_acc(programm: &[u8], acc: u8, carry: bool) -> Cpu {
+        let mut cpu = cpu_with_programm(programm);
+        cpu.accumulator = acc;
+        cpu.status_flags.set_carry(carry);
+        step(&mut cpu);
+        cpu
+    }
+
+    #[test]
+    fn adc_flags() {
+        let cpu = run_acc(&[0x69, 0x50], 0x50, false);
+        assert_eq!(cpu.accumulator, 0xA0);
+        assert_eq!(nvzc(&cpu), (true, true, false, false));
+
+        let cpu = run_acc(&[0x69, 0x01], 0xFF, false);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+
+        let cpu = run_acc(&[0x69, 0x01], 0x01, true);
+        assert_eq!(cpu.accumulator, 0x03);
+        assert_eq!(nvzc(&cpu), (false, false, false, false));
+
+        let cpu = run_acc(&[0x69, 0x90], 0xD0, false);
+        assert_eq!(cpu.accumulator, 0x60);
+        assert_eq!(nvzc(&cpu), (false, true, false, true));
+    }
+
+    #[test]
+    fn sbc_flags() {
+        let cpu = run_acc(&[0xE9, 0x03], 0x05, true);
+        assert_eq!(cpu.accumulator, 0x02);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let cpu = run_acc(&[0xE9, 0x03], 0x05, false);
+        assert_eq!(cpu.accumulator, 0x01);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let cpu = run_acc(&[0xE9, 0x01], 0x00, true);
+        assert_eq!(cpu.accumulator, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0xE9, 0xB0], 0x50, true);
+        assert_eq!(cpu.accumulator, 0xA0);
+        assert_eq!(nvzc(&cpu), (true, true, false, false));
+
+        let cpu = run_acc(&[0xE9, 0x42], 0x42, true);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+    }
+
+    #[test]
+    fn and_flags() {
+        let cpu = run_acc(&[0x29, 0xF0], 0x8F, false);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0x29, 0x0F], 0xF0, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+    }
+
+    #[test]
+    fn ora_flags() {
+        // zero is taken from the result, not the operand
+        let cpu = run_acc(&[0x09, 0x00], 0x81, false);
+        assert_eq!(cpu.accumulator, 0x81);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0x09, 0x00], 0x00, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+    }
+
+    #[test]
+    fn eor_flags() {
+        let cpu = run_acc(&[0x49, 0xFF], 0x7F, false);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0x49, 0x55], 0x55, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+    }
+
+    #[test]
+    fn asl_flags() {
+        let cpu = run_acc(&[0x0A], 0x81, false);
+        assert_eq!(cpu.accumulator, 0x02);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let cpu = run_acc(&[0x0A], 0x40, false);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let mut cpu = cpu_with_programm(&[0x06, 0x10]);
+        cpu.bus.write_to(0x10, 0x80);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+    }
+
+    #[test]
+    fn lsr_flags() {
+        let cpu = run_acc(&[0x4A], 0x01, false);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+
+        let mut cpu = cpu_with_programm(&[0x4A]);
+        cpu.accumulator = 0xFE;
+        cpu.status_flags.set_negative(true);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x7F);
+        assert_eq!(nvzc(&cpu), (false, false, false, false));
+    }
+
+    #[test]
+    fn rol_flags() {
+        let cpu = run_acc(&[0x2A], 0x80, true);
+        assert_eq!(cpu.accumulator, 0x01);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let cpu = run_acc(&[0x2A], 0x80, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+
+        let cpu = run_acc(&[0x2A], 0x40, false);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn ror_flags() {
+        let cpu = run_acc(&[0x6A], 0x01, true);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert_eq!(nvzc(&cpu), (true, false, false, true));
+
+        let cpu = run_acc(&[0x6A], 0x01, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+    }
+
+    #[test]
+    fn bit_flags() {
+        let mut cpu = cpu_with_programm(&[0x24, 0x10]);
+        cpu.accumulator = 0x01;
+        cpu.bus.write_to(0x10, 0xC0);
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (true, true, true, false));
+        // bit doesn't touch the accumulator
+        assert_eq!(cpu.accumulator, 0x01);
+
+        let mut cpu = cpu_with_programm(&[0x2C, 0x00, 0x20]);
+        cpu.accumulator = 0x01;
+        cpu.status_flags.set_negative(true);
+        cpu.status_flags.set_overflow(true);
+        cpu.bus.write_to(0x2000, 0x01);
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (false, false, false, false));
+    }
+
+    #[test]
+    fn cmp_flags() {
+        let cpu = run_acc(&[0xC9, 0x10], 0x20, false);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let cpu = run_acc(&[0xC9, 0x20], 0x20, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+
+        let cpu = run_acc(&[0xC9, 0x21], 0x20, false);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        // the overflow flag is not affected by comparisons
+        let mut cpu = cpu_with_programm(&[0xC9, 0x00]);
+        cpu.status_flags.set_overflow(true);
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (false, true, true, true));
+    }
+
+    #[test]
+    fn cpx_cpy_flags() {
+        let mut cpu = cpu_with_programm(&[0xE0, 0x80]);
+        cpu.x = 0x7F;
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let mut cpu = cpu_with_programm(&[0xC0, 0x05]);
+        cpu.y = 0x05;
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+    }
+
+    #[test]
+    fn inc_dec_flags() {
+        let mut cpu = cpu_with_programm(&[0xE6, 0x10, 0xC6, 0x10, 0xC6, 0x10]);
+        cpu.bus.write_to(0x10, 0xFF);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0xFE);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn index_inc_dec_flags() {
+        let mut cpu = cpu_with_programm(&[0xE8, 0xCA, 0xCA, 0x88]);
+        cpu.x = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0xFE);
+        step(&mut cpu);
+        assert_eq!(cpu.y, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn load_flags() {
+        let cpu = run_acc(&[0xA9, 0x80], 0x00, false);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0xA9, 0x00], 0x12, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+
+        let cpu = run_acc(&[0xA2, 0xFF], 0x00, false);
+        assert_eq!(cpu.x, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0xA0, 0x00], 0x00, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+    }
+
+    #[test]
+    fn transfer_flags() {
+        let cpu = run_acc(&[0xAA], 0x90, false);
+        assert_eq!(cpu.x, 0x90);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let cpu = run_acc(&[0xA8], 0x00, false);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+
+        let mut cpu = cpu_with_programm(&[0x8A]);
+        cpu.x = 0xF0;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0xF0);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        let mut cpu = cpu_with_programm(&[0x98]);
+        cpu.accumulator = 0x01;
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (false, false, true, false));
+
+        let mut cpu = cpu_with_programm(&[0xBA]);
+        cpu.stack_pointer = 0xFD;
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0xFD);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn txs_leaves_flags() {
+        let mut cpu = cpu_with_programm(&[0x9A]);
+        cpu.x = 0x00;
+        step(&mut cpu);
+        assert_eq!(cpu.stack_pointer, 0x00);
+        assert_eq!(nvzc(&cpu), (false, false, false, false));
+    }
+
+    #[test]
+    fn flag_instructions() {
+        let mut cpu = cpu_with_programm(&[0x38, 0xF8, 0x78, 0x18, 0xD8, 0x58, 0xB8]);
+        cpu.status_flags.set_overflow(true);
+        step(&mut cpu);
+        step(&mut cpu);
+        step(&mut cpu);
+        assert!(cpu.status_flags.carry());
+        assert!(cpu.status_flags.decimal_mode());
+        assert!(cpu.status_flags.interrupt_disable());
+        step(&mut cpu);
+        step(&mut cpu);
+        step(&mut cpu);
+        step(&mut cpu);
+        assert!(!cpu.status_flags.carry());
+        assert!(!cpu.status_flags.decimal_mode());
+        assert!(!cpu.status_flags.interrupt_disable());
+        assert!(!cpu.status_flags.overflow());
+    }
+
+    #[test]
+    fn branch_forward_and_backward() {
+        let mut cpu = cpu_with_programm(&[0xD0, 0x10]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0412);
+
+        let mut cpu = cpu_with_programm(&[0xD0, 0xFC]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x03FE);
+
+        // BEQ behaves exactly like every other branch
+        let mut cpu = cpu_with_programm(&[0xF0, 0xFE]);
+        cpu.status_flags.set_zero(true);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0400);
+    }
+
+    #[test]
+    fn branch_not_taken() {
+        // BCS with carry clear
+        let mut cpu = cpu_with_programm(&[0xB0, 0x80]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0402);
+        assert_eq!(cpu.cycles, 2);
+    }
+
+    #[test]
+    fn branch_page_crossing() {
+        // BCC with carry clear
+        let mut cpu = cpu_with_programm(&[0x90, 0x80]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0382);
+        assert_eq!(cpu.cycles, 4);
+
+        let mut cpu = cpu_with_programm(&[0x90, 0x7F]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0481);
+        assert_eq!(cpu.cycles, 3);
+    }
+
+    #[test]
+    fn branch_wraps_address_space() {
+        let mut cpu = cpu_with_programm(&[]);
+        cpu.programm_counter = 0x0010;
+        cpu.bus.write_to(0x0010, 0xD0);
+        cpu.bus.write_to(0x0011, 0xE0);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0xFFF2);
+    }
+
+    #[test]
+    fn branch_loops() {
+        // LDX #$05; loop: DEX; BNE loop
+        let mut cpu = cpu_with_programm(&[0xA2, 0x05, 0xCA, 0xD0, 0xFD]);
+        while cpu.programm_counter != 0x0405 {
+            step(&mut cpu);
+        }
+        assert_eq!(cpu.x, 0x00);
+
+        // LDA #$F0; loop: ADC #$01; BMI loop
+        let mut cpu = cpu_with_programm(&[0xA9, 0xF0, 0x69, 0x01, 0x30, 0xFC]);
+        while cpu.programm_counter != 0x0406 {
+            step(&mut cpu);
+        }
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(cpu.status_flags.carry());
+
+        // LDX #$00; loop: INX; CPX #$03; BCC loop
+        let mut cpu = cpu_with_programm(&[0xA2, 0x00, 0xE8, 0xE0, 0x03, 0x90, 0xFB]);
+        while cpu.programm_counter != 0x0407 {
+            step(&mut cpu);
+        }
+        assert_eq!(cpu.x, 0x03);
+    }
+
+    #[test]
+    fn jsr_rts() {
+        // JSR $0410; NOP; ... $0410: RTS
+        let mut programm = [0xEA; 0x11];
+        programm[..3].copy_from_slice(&[0x20, 0x10, 0x04]);
+        programm[0x10] = 0x60;
+        let mut cpu = cpu_with_programm(&programm);
+
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0410);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        // the address of the last byte of the JSR is pushed, high byte first
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x04);
+        assert_eq!(cpu.bus.read_from(0x01FE), 0x02);
+
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0403);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }
+
+    #[test]
+    fn pha_pla() {
+        // PHA; LDA #$00; PLA
+        let mut cpu = cpu_with_programm(&[0x48, 0xA9, 0x00, 0x68]);
+        cpu.accumulator = 0x85;
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x85);
+        assert_eq!(cpu.stack_pointer, 0xFE);
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x85);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn php_plp_status_layout() {
+        // PHP; PLP
+        let mut cpu = cpu_with_programm(&[0x08, 0x28]);
+        cpu.status_flags.set_carry(true);
+        cpu.status_flags.set_negative(true);
+        step(&mut cpu);
+        // NV-BDIZC with bit 5 and the break flag set
+        assert_eq!(cpu.bus.read_from(0x01FF), 0b10110001);
+
+        cpu.bus.write_to(0x01FF, 0b01001110);
+        step(&mut cpu);
+        assert_eq!(nvzc(&cpu), (false, true, true, false));
+        assert!(cpu.status_flags.decimal_mode());
+        assert!(cpu.status_flags.interrupt_disable());
+    }
+
+    #[test]
+    fn rti_pulls_status_and_return_address() {
+        let mut cpu = cpu_with_programm(&[0x40]);
+        cpu.push(0x12);
+        cpu.push(0x34);
+        cpu.push(0x00);
+        step(&mut cpu);
+        // unlike RTS the address is used as is
+        assert_eq!(cpu.programm_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }
+
+    fn cycles_of(programm: &[u8], x: u8) -> u64 {
+        let mut cpu = cpu_with_programm(programm);
+        cpu.x = x;
+        cpu.y = x;
+        step(&mut cpu);
+        cpu.cycles
+    }
+
+    #[test]
+    fn base_cycles() {
+        assert_eq!(cycles_of(&[0xA9, 0x00], 0), 2);
+        assert_eq!(cycles_of(&[0xA5, 0x00], 0), 3);
+        assert_eq!(cycles_of(&[0xAD, 0x00, 0x20], 0), 4);
+        assert_eq!(cycles_of(&[0xA1, 0x00], 0), 6);
+        assert_eq!(cycles_of(&[0x0E, 0x00, 0x20], 0), 6);
+        assert_eq!(cycles_of(&[0x20, 0x00, 0x20], 0), 6);
+        assert_eq!(cycles_of(&[0x48], 0), 3);
+        assert_eq!(cycles_of(&[0x00], 0), 7);
+    }
+
+    #[test]
+    fn page_crossing_penalty() {
+        assert_eq!(cycles_of(&[0xBD, 0x10, 0x20], 0x01), 4);
+        assert_eq!(cycles_of(&[0xBD, 0xFF, 0x20], 0x01), 5);
+        assert_eq!(cycles_of(&[0x79, 0x80, 0x20], 0x80), 5);
+        assert_eq!(cycles_of(&[0xBE, 0xFF, 0x20], 0x01), 5);
+
+        let mut cpu = cpu_with_programm(&[0xB1, 0x10]);
+        cpu.bus.write_to(0x10, 0xF0);
+        cpu.bus.write_to(0x11, 0x20);
+        cpu.y = 0x10;
+        step(&mut cpu);
+        assert_eq!(cpu.cycles, 6);
+
+        // stores and read-modify-write instructions always take the long path
+        assert_eq!(cycles_of(&[0x9D, 0x10, 0x20], 0x01), 5);
+        assert_eq!(cycles_of(&[0x9D, 0xFF, 0x20], 0x01), 5);
+        assert_eq!(cycles_of(&[0x1E, 0xFF, 0x20], 0x01), 7);
+    }
+
+    #[test]
+    fn branch_penalty() {
+        // not taken
+        assert_eq!(cycles_of(&[0xF0, 0x10], 0), 2);
+        // taken
+        assert_eq!(cycles_of(&[0xD0, 0x10], 0), 3);
+        // taken onto another page
+        assert_eq!(cycles_of(&[0xD0, 0x80], 0), 4);
+    }
+
+    #[test]
+    fn delay_loop_timing() {
+        // LDX #$0A; loop: DEX; BNE loop
+        let mut cpu = cpu_with_programm(&[0xA2, 0x0A, 0xCA, 0xD0, 0xFD]);
+        while cpu.programm_counter != 0x0405 {
+            step(&mut cpu);
+        }
+        // 2 + 10 * (2 + 3) - 1 for the last branch not taken
+        assert_eq!(cpu.cycles, 51);
+    }
+
+    fn cpu_with_illegal(programm: &[u8]) -> Cpu {
+        let mut cpu = cpu_with_programm(programm);
+        cpu.illegal_opcodes = true;
+        cpu
+    }
+
+    #[test]
+    fn illegal_opcodes_are_strict_by_default() {
+        let mut cpu = cpu_with_programm(&[0xA7, 0x10]);
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::InvalidOpcode {
+                opt_code: 0xA7,
+                addr: 0x0400
+            })
+        );
+        assert_eq!(cpu.programm_counter, 0x0400);
+    }
+
+    #[test]
+    fn lax_sax() {
+        let mut cpu = cpu_with_illegal(&[0xA7, 0x10, 0x87, 0x11]);
+        cpu.bus.write_to(0x10, 0xF3);
+        step(&mut cpu);
+        assert_eq!((cpu.accumulator, cpu.x), (0xF3, 0xF3));
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+
+        cpu.x = 0x3C;
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x11), 0x30);
+    }
+
+    #[test]
+    fn dcp_isc() {
+        let mut cpu = cpu_with_illegal(&[0xC7, 0x10, 0xE7, 0x10]);
+        cpu.accumulator = 0x41;
+        cpu.bus.write_to(0x10, 0x42);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x41);
+        assert_eq!(nvzc(&cpu), (false, false, true, true));
+
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x42);
+        assert_eq!(cpu.accumulator, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn slo_rla_sre_rra() {
+        let mut cpu = cpu_with_illegal(&[0x07, 0x10]);
+        cpu.accumulator = 0x01;
+        cpu.bus.write_to(0x10, 0x81);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x02);
+        assert_eq!(cpu.accumulator, 0x03);
+        assert!(cpu.status_flags.carry());
+
+        let mut cpu = cpu_with_illegal(&[0x27, 0x10]);
+        cpu.accumulator = 0x0F;
+        cpu.status_flags.set_carry(true);
+        cpu.bus.write_to(0x10, 0x80);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x01);
+        assert_eq!(cpu.accumulator, 0x01);
+        assert!(cpu.status_flags.carry());
+
+        let mut cpu = cpu_with_illegal(&[0x47, 0x10]);
+        cpu.accumulator = 0xFF;
+        cpu.bus.write_to(0x10, 0x03);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x01);
+        assert_eq!(cpu.accumulator, 0xFE);
+        assert!(cpu.status_flags.carry());
+
+        // the carry shifted out of the ROR is added in
+        let mut cpu = cpu_with_illegal(&[0x67, 0x10]);
+        cpu.accumulator = 0x10;
+        cpu.bus.write_to(0x10, 0x03);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x01);
+        assert_eq!(cpu.accumulator, 0x12);
+        assert!(!cpu.status_flags.carry());
+    }
+
+    #[test]
+    fn anc_alr_arr_sbx() {
+        let mut cpu = cpu_with_illegal(&[0x0B, 0x80]);
+        cpu.accumulator = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x80);
+        assert_eq!(nvzc(&cpu), (true, false, false, true));
+
+        let mut cpu = cpu_with_illegal(&[0x4B, 0x03]);
+        cpu.accumulator = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x01);
+        assert_eq!(nvzc(&cpu), (false, false, false, true));
+
+        let mut cpu = cpu_with_illegal(&[0x6B, 0xFF]);
+        cpu.accumulator = 0xC0;
+        cpu.status_flags.set_carry(true);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0xE0);
+        assert_eq!(nvzc(&cpu), (true, false, false, true));
+
+        let mut cpu = cpu_with_illegal(&[0x6B, 0xFF]);
+        cpu.accumulator = 0x40;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x20);
+        assert_eq!(nvzc(&cpu), (false, true, false, false));
+
+        let mut cpu = cpu_with_illegal(&[0xCB, 0x02]);
+        cpu.accumulator = 0x0F;
+        cpu.x = 0xF1;
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0xFF);
+        assert_eq!(nvzc(&cpu), (true, false, false, false));
+    }
+
+    #[test]
+    fn arr_decimal() {
+        let mut cpu = cpu_with_illegal(&[0x6B, 0xFF]);
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.accumulator = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0xD5);
+        assert!(cpu.status_flags.carry());
+    }
+
+    #[test]
+    fn illegal_nops() {
+        let mut cpu =
+            cpu_with_illegal(&[0x1A, 0x80, 0x00, 0x04, 0x00, 0x14, 0x00, 0x0C, 0x00, 0x00]);
+        cpu.accumulator = 0x12;
+        for (pc, cycles) in [
+            (0x0401, 2),
+            (0x0403, 4),
+            (0x0405, 7),
+            (0x0407, 11),
+            (0x040A, 15),
+        ] {
+            step(&mut cpu);
+            assert_eq!(cpu.programm_counter, pc);
+            assert_eq!(cpu.cycles, cycles);
+        }
+        assert_eq!(cpu.accumulator, 0x12);
+
+        let mut cpu = cpu_with_illegal(&[0x1C, 0xFF, 0x20]);
+        cpu.x = 0x01;
+        step(&mut cpu);
+        assert_eq!(cpu.cycles, 5);
+    }
+
+    #[test]
+    fn jam_halts() {
+        let mut cpu = cpu_with_illegal(&[0x02, 0xEA]);
+        step(&mut cpu);
+        assert!(cpu.jammed);
+        assert_eq!(cpu.programm_counter, 0x0400);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0400);
+    }
+
+    fn cpu_with_cmos(programm: &[u8]) -> Cpu {
+        let mut cpu = cpu_with_programm(programm);
+        cpu.variant = Variant::Cmos65C02;
+        cpu
+    }
+
+    #[test]
+    fn cmos_executes_every_opcode() {
+        for opt_code in 0..=0xFF {
+            let mut cpu = cpu_with_cmos(&[opt_code, 0x00, 0x00]);
+            step(&mut cpu);
+            assert!(cpu.cycles > 0, "opcode {opt_code:02x}");
+        }
+    }
+
+    #[test]
+    fn cmos_jmp_ind_does_not_wrap() {
+        let mut cpu = cpu_with_cmos(&[0x6C, 0xFF, 0x20]);
+        cpu.bus.write_to(0x20FF, 0x34);
+        cpu.bus.write_to(0x2100, 0x12);
+        cpu.bus.write_to(0x2000, 0x56);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x1234);
+        assert_eq!(cpu.cycles, 6);
+    }
+
+    #[test]
+    fn cmos_jmp_absxind() {
+        let mut cpu = cpu_with_cmos(&[0x7C, 0x00, 0x20]);
+        cpu.x = 0x04;
+        cpu.bus.write_to(0x2004, 0x34);
+        cpu.bus.write_to(0x2005, 0x12);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x1234);
+    }
+
+    #[test]
+    fn cmos_zpgind() {
+        let mut cpu = cpu_with_cmos(&[0xB2, 0xFF, 0x92, 0x10]);
+        cpu.bus.write_to(0xFF, 0x74);
+        cpu.bus.write_to(0x00, 0x20);
+        cpu.bus.write_to(0x2074, 0x42);
+        cpu.bus.write_to(0x10, 0x00);
+        cpu.bus.write_to(0x11, 0x30);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x42);
+        assert_eq!(cpu.cycles, 5);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x3000), 0x42);
+        assert_eq!(cpu.programm_counter, 0x0404);
+    }
+
+    #[test]
+    fn cmos_bra() {
+        let mut cpu = cpu_with_cmos(&[0x80, 0x10]);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0412);
+        assert_eq!(cpu.cycles, 3);
+    }
+
+    #[test]
+    fn cmos_phx_phy_plx_ply() {
+        let mut cpu = cpu_with_cmos(&[0xDA, 0x5A, 0xFA, 0x7A]);
+        cpu.x = 0x80;
+        cpu.y = 0x00;
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x01FF), 0x80);
+        assert_eq!(cpu.bus.read_from(0x01FE), 0x00);
+        step(&mut cpu);
+        assert_eq!(cpu.x, 0x00);
+        assert!(cpu.status_flags.zero());
+        step(&mut cpu);
+        assert_eq!(cpu.y, 0x80);
+        assert!(cpu.status_flags.negative());
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(cpu.cycles, 14);
+    }
+
+    #[test]
+    fn cmos_stz() {
+        let mut cpu = cpu_with_cmos(&[0x64, 0x10, 0x9E, 0x00, 0x20]);
+        cpu.x = 0x01;
+        cpu.bus.write_to(0x10, 0xFF);
+        cpu.bus.write_to(0x2001, 0xFF);
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0x00);
+        assert_eq!(cpu.bus.read_from(0x2001), 0x00);
+        assert_eq!(cpu.cycles, 8);
+    }
+
+    #[test]
+    fn cmos_tsb_trb() {
+        let mut cpu = cpu_with_cmos(&[0x04, 0x10, 0x14, 0x10]);
+        cpu.accumulator = 0b00001111;
+        cpu.bus.write_to(0x10, 0b11110000);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0b11111111);
+        assert!(cpu.status_flags.zero());
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0b11110000);
+        assert!(!cpu.status_flags.zero());
+    }
+
+    #[test]
+    fn cmos_inc_dec_accumulator() {
+        let mut cpu = cpu_with_cmos(&[0x1A, 0x3A, 0x3A]);
+        cpu.accumulator = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(cpu.status_flags.zero());
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0xFE);
+        assert!(cpu.status_flags.negative());
+        assert_eq!(cpu.cycles, 6);
+    }
+
+    #[test]
+    fn cmos_bit_modes() {
+        let mut cpu = cpu_with_cmos(&[0x89, 0xC0, 0x3C, 0x00, 0x20]);
+        cpu.accumulator = 0x01;
+        step(&mut cpu);
+        assert!(cpu.status_flags.zero());
+        assert!(!cpu.status_flags.negative());
+        assert!(!cpu.status_flags.overflow());
+
+        cpu.x = 0x01;
+        cpu.bus.write_to(0x2001, 0xC1);
+        step(&mut cpu);
+        assert!(!cpu.status_flags.zero());
+        assert!(cpu.status_flags.negative());
+        assert!(cpu.status_flags.overflow());
+    }
+
+    #[test]
+    fn cmos_rmb_smb() {
+        let mut cpu = cpu_with_cmos(&[0x37, 0x10, 0xC7, 0x10]);
+        cpu.bus.write_to(0x10, 0xFF);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0b11110111);
+        cpu.bus.write_to(0x10, 0x00);
+        step(&mut cpu);
+        assert_eq!(cpu.bus.read_from(0x10), 0b00010000);
+        assert_eq!(cpu.cycles, 10);
+    }
+
+    #[test]
+    fn cmos_bbr_bbs() {
+        let mut cpu = cpu_with_cmos(&[0x0F, 0x10, 0x10, 0x8F, 0x10, 0x10]);
+        cpu.bus.write_to(0x10, 0x01);
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0403);
+        assert_eq!(cpu.cycles, 5);
+
+        cpu.programm_counter = 0x0403;
+        step(&mut cpu);
+        assert_eq!(cpu.programm_counter, 0x0416);
+        assert_eq!(cpu.cycles, 11);
+    }
+
+    #[test]
+    fn cmos_stp_and_wai() {
+        let mut cpu = cpu_with_cmos(&[0xDB]);
+        step(&mut cpu);
+        assert!(cpu.jammed);
+
+        let mut cpu = cpu_with_cmos(&[0xCB, 0xEA]);
+        cpu.status_flags.set_interrupt_disable(true);
+        cpu.pulse();
+        assert!(cpu.waiting);
+        cpu.pulse();
+        assert_eq!(cpu.programm_counter, 0x0401);
+        // a masked irq only wakes the cpu up and execution continues after WAI
+        cpu.assert_irq();
+        cpu.pulse();
+        assert!(!cpu.waiting);
+        assert_eq!(cpu.programm_counter, 0x0402);
+    }
+
+    #[test]
+    fn cmos_undefined_opcodes_are_nops() {
+        let mut cpu = cpu_with_cmos(&[0x03, 0x02, 0x00, 0x5C, 0x00, 0x00, 0xFC, 0x00, 0x00]);
+        for (pc, cycles) in [(0x0401, 1), (0x0403, 3), (0x0406, 11), (0x0409, 15)] {
+            step(&mut cpu);
+            assert_eq!(cpu.programm_counter, pc);
+            assert_eq!(cpu.cycles, cycles);
+        }
+    }
+
+    #[test]
+    fn cmos_shift_page_penalty() {
+        let mut cpu = cpu_with_cmos(&[0x1E, 0x00, 0x20, 0x1E, 0xFF, 0x20]);
+        cpu.x = 0x01;
+        step(&mut cpu);
+        assert_eq!(cpu.cycles, 6);
+        step(&mut cpu);
+        assert_eq!(cpu.cycles, 13);
+    }
+
+    #[test]
+    fn cmos_decimal_flags() {
+        // 0x99 + 0x01 = 0x00, the nmos 6502 leaves Z clear and N set here
+        let mut cpu = cpu_with_cmos(&[0x69, 0x01]);
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.accumulator = 0x99;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(cpu.status_flags.zero());
+        assert!(!cpu.status_flags.negative());
+        assert!(cpu.status_flags.carry());
+        assert_eq!(cpu.cycles, 3);
+
+        let mut cpu = cpu_with_programm(&[0x69, 0x01]);
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.accumulator = 0x99;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(!cpu.status_flags.zero());
+        assert!(cpu.status_flags.negative());
+        assert_eq!(cpu.cycles, 2);
+    }
+
+    #[test]
+    fn cmos_decimal_cycle_reads_next_instruction() {
+        let mut cpu = cpu_with_cmos(&[0x6D, 0x00, 0x20]);
+        cpu.status_flags.set_decimal_mode(true);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses().len(), 5);
+        assert_eq!(cpu.bus_accesses()[4], BusAccess::Read(0x0403, 0x00));
+    }
+
+    #[test]
+    fn cmos_decimal_subtract() {
+        let mut cpu = cpu_with_cmos(&[0xE9, 0x01, 0xE9, 0x01]);
+        cpu.status_flags.set_decimal_mode(true);
+        cpu.status_flags.set_carry(true);
+        cpu.accumulator = 0x00;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x99);
+        assert!(!cpu.status_flags.carry());
+        assert!(cpu.status_flags.negative());
+
+        cpu.status_flags.set_carry(true);
+        cpu.accumulator = 0x01;
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x00);
+        assert!(cpu.status_flags.zero());
+        assert_eq!(cpu.cycles, 6);
+    }
+
+    #[test]
+    fn ricoh2a03_ignores_decimal_mode() {
+        let mut cpu = cpu_with_programm(&[0xF8, 0x69, 0x19, 0xE9, 0x01]);
+        cpu.variant = Variant::Ricoh2A03;
+        cpu.accumulator = 0x09;
+        step(&mut cpu);
+        assert!(cpu.status_flags.decimal_mode());
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x22);
+        assert_eq!(cpu.cycles, 4);
+        cpu.status_flags.set_carry(true);
+        step(&mut cpu);
+        assert_eq!(cpu.accumulator, 0x21);
+    }
+
+    #[test]
+    fn variant_selects_decoding() {
+        // BRA on the 65C02, a two byte NOP on the nmos parts
+        for (variant, pc) in [
+            (Variant::Nmos6502, 0x0402),
+            (Variant::Cmos65C02, 0x0412),
+            (Variant::Ricoh2A03, 0x0402),
+            (Variant::Mos6507, 0x0402),
+            (Variant::Mos6510, 0x0402),
+        ] {
+            let mut cpu = cpu_with_programm(&[0x80, 0x10]);
+            cpu.variant = variant;
+            cpu.illegal_opcodes = true;
+            step(&mut cpu);
+            assert_eq!(cpu.programm_counter, pc, "{variant:?}");
+        }
+    }
+
+    #[test]
+    fn iny_increments_y() {
+        let mut cpu = cpu_with_programm(&[0xC8]);
+        cpu.y = 0xFF;
+        step(&mut cpu);
+        assert_eq!(cpu.y, 0x00);
+        assert_eq!(cpu.x, 0x00);
+        assert!(cpu.status_flags.zero());
+    }
+
+    #[test]
+    fn opcode_tables() {
+        let nmos = opcode_table(Variant::Nmos6502);
+        assert_eq!(nmos.iter().filter(|opcode| !opcode.illegal).count(), 151);
+        assert_eq!(nmos[0xC8].mnemonic, "INY");
+        assert_eq!(nmos[0xBD].bytes, 3);
+        assert_eq!(nmos[0xBD].cycles, 4);
+
+        let cmos = opcode_table(Variant::Cmos65C02);
+        assert!(cmos.iter().all(|opcode| !opcode.illegal));
+        assert_eq!(cmos[0x80].mnemonic, "BRA");
+        assert_eq!(cmos[0xFF].mnemonic, "BBS7");
+    }
+
+    #[test]
+    fn disassemble_modes() {
+        let mut cpu = cpu_with_programm(&[
+            0xA9, 0x42, 0xB1, 0x20, 0x6C, 0x34, 0x12, 0xD0, 0xFE, 0x0A, 0xEA,
+        ]);
+        let mut addr = 0x0400;
+        for expected in [
+            "LDA #$42",
+            "LDA ($20),Y",
+            "JMP ($1234)",
+            "BNE $0407",
+            "ASL A",
+            "NOP",
+        ] {
+            let (text, bytes) = disassemble(&cpu, addr);
+            assert_eq!(text, expected);
+            addr += bytes as u16;
+        }
+
+        cpu.variant = Variant::Cmos65C02;
+        cpu.bus.write_to(0x0400, 0x8F);
+        cpu.bus.write_to(0x0401, 0x10);
+        cpu.bus.write_to(0x0402, 0x02);
+        assert_eq!(disassemble(&cpu, 0x0400), ("BBS0 $10,$0405".to_string(), 3));
+    }
+
+    #[test]
+    fn programm_counter_wraps() {
+        let mut cpu = cpu_with_programm(&[]);
+        cpu.bus.write_to(0xFFFE, 0xA9);
+        cpu.bus.write_to(0xFFFF, 0x42);
+        cpu.bus.write_to(0x0000, 0xEA);
+        cpu.programm_counter = 0xFFFE;
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.programm_counter, 0x0000);
+        assert_eq!(cpu.accumulator, 0x42);
+
+        let mut cpu = cpu_with_programm(&[0xBD, 0xFF, 0xFF]);
+        cpu.x = 0x02;
+        cpu.bus.write_to(0x0001, 0x37);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.accumulator, 0x37);
+    }
+
+    #[test]
+    fn jammed_cpu_reports_error() {
+        let mut cpu = cpu_with_illegal(&[0x02]);
+        assert_eq!(cpu.step(), Err(CpuError::Jammed { addr: 0x0400 }));
+        assert_eq!(cpu.step(), Err(CpuError::Jammed { addr: 0x0400 }));
+
+        let mut cpu = cpu_with_programm(&[0xDB]);
+        cpu.variant = Variant::Cmos65C02;
+        assert_eq!(cpu.step(), Err(CpuError::Jammed { addr: 0x0400 }));
+    }
+
+    #[test]
+    fn every_access_is_a_cycle() {
+        for variant in [Variant::Nmos6502, Variant::Cmos65C02] {
+            for (opt_code, opcode) in opcode_table(variant).iter().enumerate() {
+                // taken branches depend on the flags, the jams never finish
+                if opcode.mem_mode == MemMode::REL
+                    || opcode.mem_mode == MemMode::ZPGREL
+                    || opcode.mnemonic == "JAM"
+                {
+                    continue;
+                }
+                let mut cpu = cpu_with_programm(&[opt_code as u8, 0x00, 0x00]);
+                cpu.variant = variant;
+                cpu.illegal_opcodes = true;
+                if cpu.step().is_err() {
+                    continue;
+                }
+                assert_eq!(
+                    cpu.bus_accesses().len() as u64,
+                    opcode.cycles as u64,
+                    "{variant:?} opcode {opt_code:02x}"
+                );
+                assert_eq!(cpu.cycles, opcode.cycles as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn implied_reads_next_byte() {
+        let mut cpu = cpu_with_programm(&[0xEA, 0x42]);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(
+            cpu.bus_accesses(),
+            [BusAccess::Read(0x0400, 0xEA), BusAccess::Read(0x0401, 0x42)]
+        );
+    }
+
+    #[test]
+    fn read_modify_write_bus_cycles() {
+        let mut cpu = cpu_with_programm(&[0xEE, 0x00, 0x20]);
+        cpu.bus.write_to(0x2000, 0x41);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(
+            cpu.bus_accesses(),
+            [
+                BusAccess::Read(0x0400, 0xEE),
+                BusAccess::Read(0x0401, 0x00),
+                BusAccess::Read(0x0402, 0x20),
+                BusAccess::Read(0x2000, 0x41),
+                BusAccess::Write(0x2000, 0x41),
+                BusAccess::Write(0x2000, 0x42),
+            ]
+        );
+
+        // the 65C02 reads the value twice instead of writing it back
+        let mut cpu = cpu_with_cmos(&[0xEE, 0x00, 0x20]);
+        cpu.bus.write_to(0x2000, 0x41);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses()[4], BusAccess::Read(0x2000, 0x41));
+        assert_eq!(cpu.bus_accesses()[5], BusAccess::Write(0x2000, 0x42));
+    }
+
+    #[test]
+    fn indexed_dummy_reads() {
+        // stores always read the address before the carry is fixed up
+        let mut cpu = cpu_with_programm(&[0x9D, 0x80, 0x20]);
+        cpu.x = 0x90;
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses()[3], BusAccess::Read(0x2010, 0x00));
+        assert_eq!(cpu.bus_accesses()[4], BusAccess::Write(0x2110, 0x00));
+
+        let mut cpu = cpu_with_programm(&[0x9D, 0x00, 0x20]);
+        cpu.x = 0x10;
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses()[3], BusAccess::Read(0x2010, 0x00));
+        assert_eq!(cpu.cycles, 5);
+
+        // loads only when a page is crossed
+        let mut cpu = cpu_with_programm(&[0xBD, 0x00, 0x20]);
+        cpu.x = 0x10;
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses().len(), 4);
+
+        // zero page indexing reads the base address first
+        let mut cpu = cpu_with_programm(&[0xB5, 0x80]);
+        cpu.x = 0x10;
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.bus_accesses()[2], BusAccess::Read(0x0080, 0x00));
+        assert_eq!(cpu.bus_accesses()[3], BusAccess::Read(0x0090, 0x00));
+    }
+
+    #[test]
+    fn branch_bus_cycles() {
+        let mut cpu = cpu_with_programm(&[]);
+        cpu.programm_counter = 0x04F0;
+        cpu.bus.write_to(0x04F0, 0xD0);
+        cpu.bus.write_to(0x04F1, 0x20);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.programm_counter, 0x0512);
+        assert_eq!(
+            cpu.bus_accesses(),
+            [
+                BusAccess::Read(0x04F0, 0xD0),
+                BusAccess::Read(0x04F1, 0x20),
+                BusAccess::Read(0x04F2, 0x00),
+                BusAccess::Read(0x0412, 0x00),
+            ]
+        );
+    }
+
+    #[test]
+    fn subroutine_bus_cycles() {
+        let mut cpu = cpu_with_programm(&[0x20, 0x00, 0x20]);
+        cpu.bus.write_to(0x2000, 0x60);
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(
+            cpu.bus_accesses(),
+            [
+                BusAccess::Read(0x0400, 0x20),
+                BusAccess::Read(0x0401, 0x00),
+                BusAccess::Read(0x01FF, 0x00),
+                BusAccess::Write(0x01FF, 0x04),
+                BusAccess::Write(0x01FE, 0x02),
+                BusAccess::Read(0x0402, 0x20),
+            ]
+        );
+
+        assert_eq!(cpu.step(), Ok(()));
+        assert_eq!(cpu.programm_counter, 0x0403);
+        assert_eq!(
+            cpu.bus_accesses(),
+            [
+                BusAccess::Read(0x2000, 0x60),
+                BusAccess::Read(0x2001, 0x00),
+                BusAccess::Read(0x01FD, 0x00),
+                BusAccess::Read(0x01FE, 0x02),
+                BusAccess::Read(0x01FF, 0x04),
+                BusAccess::Read(0x0402, 0x20),
+            ]
+        );
+    }
 
     #[test]
     fn wrap_around() {
-        let result: u8 = (0xFF + 0x80 % 256) as u8;
+        let result: u8 = ((0xFF + 0x80) % 256) as u8;
         assert_eq!(result, 0x07F_u8);
     }
 
@@ -1,8 +1,13 @@
+pub mod address;
 pub mod bus;
 pub mod clock;
 pub mod cpu;
 pub mod display;
+pub mod error;
 #[allow(non_snake_case)]
-pub(crate) mod instructionset;
-pub(crate) mod ram;
+pub mod instructionset;
+pub mod ram;
+pub(crate) mod random;
 pub mod rom;
+pub mod status;
+pub mod variant;
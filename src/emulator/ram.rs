@@ -1,13 +1,37 @@
+use crate::emulator::bus::BusDevice;
+use crate::emulator::random::Random;
+
+// addresses are offsets into the window the ram is attached to
 pub struct Ram {
-    pub mem: [u8; 32767],
+    pub mem: Vec<u8>,
 }
 
 impl Ram {
-    pub fn read(&self, addr: u16) -> u8 {
-        self.mem[addr as usize]
+    pub fn new(size: usize) -> Ram {
+        Self { mem: vec![0; size] }
+    }
+}
+
+// a window larger than the chip has to be mirrored, attach checks the size
+impl BusDevice for Ram {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
     }
 
-    pub fn write(&mut self, addr: u16, data: u8) {
+    fn write(&mut self, addr: u16, data: u8) {
         self.mem[addr as usize] = data;
     }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.mem.len())
+    }
+
+    // ram holds garbage after power on
+    fn power_on(&mut self, seed: u64) {
+        Random::new(seed).fill(&mut self.mem);
+    }
 }
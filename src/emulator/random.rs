@@ -0,0 +1,29 @@
+// small deterministic generator for the power on state, the same seed always gives the same garbage
+// splitmix64, good enough for filling memory and cheap to seed with anything including 0
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Random {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    pub(crate) fn fill(&mut self, mem: &mut [u8]) {
+        for byte in mem {
+            *byte = self.next_u8();
+        }
+    }
+}
@@ -1,14 +1,39 @@
+use crate::emulator::bus::BusDevice;
+
+// addresses are offsets into the window the rom is attached to
 #[derive(Debug)]
 pub struct Rom {
-    pub mem: [u8; 65535],
+    pub mem: Vec<u8>,
 }
 
 impl Rom {
-    pub fn read(&self, addr: u16) -> u8 {
-        self.mem[addr as usize]
+    pub fn new(size: usize) -> Rom {
+        Self { mem: vec![0; size] }
+    }
+
+    // a rom image, the first byte ends up at the start of the window
+    pub fn from_bytes(bytes: &[u8]) -> Rom {
+        Self {
+            mem: bytes.to_vec(),
+        }
+    }
+}
+
+// a window larger than the chip has to be mirrored, attach checks the size
+impl BusDevice for Rom {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
     }
 
-    pub fn write(&mut self, addr: u16, data: u8) {
+    fn write(&mut self, addr: u16, data: u8) {
         self.mem[addr as usize] = data;
     }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.mem.len())
+    }
 }
@@ -0,0 +1,129 @@
+// processor status register, laid out like the hardware: NV-BDIZC
+//
+// bit 5 and the break flag are not real flip-flops inside the cpu,
+// they only show up in the copy of the register that gets pushed to the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusRegister {
+    bits: u8,
+}
+
+pub const CARRY_FLAG: u8 = 0b00000001;
+pub const ZERO_FLAG: u8 = 0b00000010;
+pub const INTERRUPT_DISABLE_FLAG: u8 = 0b00000100;
+pub const DECIMAL_MODE_FLAG: u8 = 0b00001000;
+pub const BREAK_COMMAND_FLAG: u8 = 0b00010000;
+pub const UNUSED_FLAG: u8 = 0b00100000;
+pub const OVERFLOW_FLAG: u8 = 0b01000000;
+pub const NEGATIVE_FLAG: u8 = 0b10000000;
+
+macro_rules! flag_accessors {
+    ($($get:ident, $set:ident, $mask:ident;)*) => {
+        $(
+            pub fn $get(&self) -> bool {
+                self.bits & $mask != 0
+            }
+
+            pub fn $set(&mut self, val: bool) {
+                if val {
+                    self.bits |= $mask;
+                } else {
+                    self.bits &= !$mask;
+                }
+            }
+        )*
+    };
+}
+
+impl Default for StatusRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusRegister {
+    pub fn new() -> StatusRegister {
+        // reading the register back always shows bit 5 set
+        Self {
+            bits: UNUSED_FLAG | BREAK_COMMAND_FLAG,
+        }
+    }
+
+    flag_accessors! {
+        carry, set_carry, CARRY_FLAG;
+        zero, set_zero, ZERO_FLAG;
+        interrupt_disable, set_interrupt_disable, INTERRUPT_DISABLE_FLAG;
+        decimal_mode, set_decimal_mode, DECIMAL_MODE_FLAG;
+        break_command, set_break_command, BREAK_COMMAND_FLAG;
+        overflow, set_overflow, OVERFLOW_FLAG;
+        negative, set_negative, NEGATIVE_FLAG;
+    }
+
+    pub fn from_byte(bits: u8) -> StatusRegister {
+        Self { bits }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self.bits
+    }
+
+    // the value PHP/BRK (break_flag set) or IRQ/NMI (break_flag clear) push to the stack
+    pub fn to_stack_byte(self, break_flag: bool) -> u8 {
+        let bits = self.bits | UNUSED_FLAG;
+        if break_flag {
+            bits | BREAK_COMMAND_FLAG
+        } else {
+            bits & !BREAK_COMMAND_FLAG
+        }
+    }
+
+    // PLP/RTI only restore the real flags, bit 5 and the break flag are ignored
+    pub fn set_from_stack_byte(&mut self, stack_byte: u8) {
+        let kept = UNUSED_FLAG | BREAK_COMMAND_FLAG;
+        self.bits = (self.bits & kept) | (stack_byte & !kept);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_conversion_is_lossless() {
+        for bits in 0..=0xFF {
+            assert_eq!(StatusRegister::from_byte(bits).to_byte(), bits);
+        }
+    }
+
+    #[test]
+    fn nv_bdizc_layout() {
+        let mut status = StatusRegister::from_byte(0x00);
+        status.set_carry(true);
+        status.set_negative(true);
+        assert_eq!(status.to_byte(), 0b10000001);
+
+        let status = StatusRegister::from_byte(0b01001110);
+        assert!(status.overflow());
+        assert!(status.decimal_mode());
+        assert!(status.interrupt_disable());
+        assert!(status.zero());
+        assert!(!status.carry());
+        assert!(!status.negative());
+    }
+
+    #[test]
+    fn stack_byte_break_flag() {
+        let status = StatusRegister::from_byte(CARRY_FLAG);
+        assert_eq!(status.to_stack_byte(true), 0b00110001);
+        assert_eq!(status.to_stack_byte(false), 0b00100001);
+    }
+
+    #[test]
+    fn pulling_ignores_break_and_bit_5() {
+        let mut status = StatusRegister::new();
+        status.set_from_stack_byte(0b11001111);
+        assert_eq!(status.to_byte(), 0b11111111);
+
+        status.set_from_stack_byte(0x00);
+        assert_eq!(status.to_byte(), 0b00110000);
+    }
+}
@@ -0,0 +1,103 @@
+// the members of the 6502 family we can emulate
+//
+// they all share the nmos core, the differences are in decoding,
+// decimal mode and what the address bus looks like from the outside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    // the original MOS 6502
+    #[default]
+    Nmos6502,
+    // WDC 65C02 with the extended instruction set and fixed bugs
+    Cmos65C02,
+    // Ricoh 2A03/2A07 of the NES, the decimal flag exists but ADC/SBC ignore it
+    Ricoh2A03,
+    // MOS 6507 of the Atari 2600, only 13 address lines are bonded out
+    Mos6507,
+    // MOS 6510 of the C64, an i/o port sits at $0000/$0001
+    Mos6510,
+}
+
+impl Variant {
+    pub fn is_cmos(self) -> bool {
+        self == Variant::Cmos65C02
+    }
+
+    pub fn has_decimal_mode(self) -> bool {
+        self != Variant::Ricoh2A03
+    }
+
+    pub fn has_io_port(self) -> bool {
+        self == Variant::Mos6510
+    }
+
+    // the address lines that actually reach the bus
+    pub fn address_mask(self) -> u16 {
+        match self {
+            Variant::Mos6507 => 0x1FFF,
+            _ => 0xFFFF,
+        }
+    }
+}
+
+// the on-chip i/o port of the 6510
+// $0000 is the data direction register (1 = output), $0001 the port itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoPort {
+    pub direction: u8,
+    pub output: u8,
+    // level of the pins that are configured as inputs, driven by the board
+    pub input: u8,
+}
+
+impl Default for IoPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoPort {
+    pub fn new() -> IoPort {
+        // all pins start out as inputs, unconnected ones are pulled high
+        Self {
+            direction: 0x00,
+            output: 0x00,
+            input: 0xFF,
+        }
+    }
+
+    // the level seen on the pins, output lines from the cpu and input lines from the board
+    pub fn pins(&self) -> u8 {
+        (self.output & self.direction) | (self.input & !self.direction)
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        if addr == 0x0000 {
+            self.direction
+        } else {
+            self.pins()
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if addr == 0x0000 {
+            self.direction = data;
+        } else {
+            self.output = data;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_port_direction() {
+        let mut port = IoPort::new();
+        port.input = 0b10100000;
+        port.write(0x0000, 0b00001111);
+        port.write(0x0001, 0b01010101);
+        assert_eq!(port.read(0x0000), 0b00001111);
+        assert_eq!(port.read(0x0001), 0b10100101);
+    }
+}
@@ -1,28 +1,34 @@
-use r6502::emulator::{
-    bus::{Bus, Device},
-    clock::Clock,
-    cpu::Cpu,
-    display::Display,
-    rom::Rom,
-};
+use r6502::emulator::{bus::Bus, clock::Clock, cpu::Cpu, display::Display, ram::Ram, rom::Rom};
 
 fn main() {
     println!("Hello, world!");
 
-    let mut prog_rom = Rom { mem: [0; 65535] };
     let programm = read_bytes_from_file("./asm/a.out");
-    for (i, v) in programm.iter().enumerate() {
-        prog_rom.mem[i] = *v;
-    }
+    let mut clock = board(programm);
+    let reason = clock.start();
+    println!("\nstopped: {reason}");
+}
+
+// the image covers the address space from 0x0000, the rom only holds the part from 0x0400 on
+// vasm stops the image after the last byte it emits, so it's padded to the full 64K first
+fn board(mut programm: Vec<u8>) -> Clock {
+    programm.resize(0x10000, 0);
+    let prog_rom = Rom::from_bytes(&programm[0x400..]);
 
     let display = Display::new();
 
     let mut bus = Bus::new();
-    bus.attach(Device::Rom(prog_rom), (0x400, 0xFFFF));
-    bus.attach(Device::Display(display), (0x200, 0x200));
+    bus.attach(Box::new(Ram::new(0x200)), (0x0000, 0x01FF))
+        .expect("invalid memory map");
+    bus.attach(Box::new(prog_rom), (0x400, 0xFFFF))
+        .expect("invalid memory map");
+    bus.attach(Box::new(display), (0x200, 0x200))
+        .expect("invalid memory map");
     let cpu = Cpu::new(bus);
     let mut clock = Clock::new(cpu);
-    clock.start();
+    // the programm ends in an idle loop, nothing runs on interrupts
+    clock.trap_halts = true;
+    clock
 }
 
 fn read_bytes_from_file(path: &str) -> Vec<u8> {
@@ -31,7 +37,40 @@ fn read_bytes_from_file(path: &str) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
+    use super::board;
+    use r6502::emulator::clock::StopReason;
+
+    #[test]
+    fn boots_short_images() {
+        // JMP $0400 and the reset vector, the image ends at 0xFFFD like the one vasm writes
+        let mut programm = vec![0x00; 0xFFFE];
+        programm[0x0400..0x0403].copy_from_slice(&[0x4C, 0x00, 0x04]);
+        programm[0xFFFC..].copy_from_slice(&[0x00, 0x04]);
+        let mut clock = board(programm);
+        assert_eq!(clock.start(), StopReason::Trap { addr: 0x0400 });
+
+        // not even reaching the rom
+        let clock = board(vec![0xEA; 0x10]);
+        assert_eq!(clock.cpu().peek(0x0400), 0x00);
+    }
+
+    #[test]
+    fn hello_world_stops() {
+        // asm/hello_world.s as vasm assembles it
+        let mut programm = vec![0x00; 0xFFFE];
+        programm[0x0400..0x0412].copy_from_slice(&[
+            0xA2, 0x00, 0xBD, 0x12, 0x04, 0xF0, 0x07, 0x8D, 0x00, 0x02, 0xE8, 0x4C, 0x02, 0x04,
+            0xEA, 0x4C, 0x0E, 0x04,
+        ]);
+        programm[0x0412..0x0420].copy_from_slice(b"Hello, World!\0");
+        programm[0xFFFC..].copy_from_slice(&[0x00, 0x04]);
+        let mut clock = board(programm);
+        assert!(matches!(clock.start(), StopReason::Trap { .. }));
+        assert_eq!(clock.cpu().x, 13);
+    }
+
     #[test]
+    #[allow(clippy::unnecessary_cast)]
     fn bithshift() {
         let num: u16 = 0x00 as u16 | (0x30 as u16) << 8;
         println!("{num:x}");
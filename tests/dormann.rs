@@ -0,0 +1,171 @@
+// Klaus Dormann's functional tests, https://github.com/Klaus2m5/6502_65C02_functional_tests
+//
+// the binaries are not checked in, drop them into tests/roms and run
+// cargo test --test dormann -- --ignored
+// - 6502_functional_test.bin        prebuilt one from bin_files, loaded at 0x0000, starts at 0x0400
+// - 65C02_extended_opcodes_test.bin prebuilt one from bin_files, loaded at 0x0000, starts at 0x0400
+// - 6502_decimal_test.bin           assembled to 0x0200, ERROR at 0x000B
+// the success addresses belong to the prebuilt binaries, an own assembly with other options
+// moves them, take the address of the success label from the listing then
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use r6502::emulator::{
+    bus::Bus, cpu::Cpu, instructionset::disassemble, rom::Rom, variant::Variant,
+};
+
+// the functional tests keep the number of the running test case here
+const TEST_CASE: u16 = 0x0200;
+// where the prebuilt binaries trap when every test passed
+const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+const EXTENDED_OPCODES_SUCCESS: u16 = 0x24F1;
+// how many instructions are shown when a trap is hit
+const HISTORY: usize = 20;
+// the functional test needs about 30 million instructions, anything beyond is a runaway
+const MAX_INSTRUCTIONS: u64 = 200_000_000;
+
+// the state before an instruction, only formatted when a test fails
+struct Step {
+    pc: u16,
+    opt_code: u8,
+    registers: [u8; 5],
+}
+
+struct Trap {
+    addr: u16,
+    history: VecDeque<Step>,
+}
+
+fn load(file: &str, load_addr: u16, start: u16, variant: Variant) -> Cpu {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/roms")
+        .join(file);
+    let image =
+        std::fs::read(&path).unwrap_or_else(|err| panic!("can't read {}: {err}", path.display()));
+
+    let mut mem = Rom::new(0x10000);
+    let load_addr = load_addr as usize;
+    mem.mem[load_addr..load_addr + image.len()].copy_from_slice(&image);
+    let mut bus = Bus::new();
+    bus.attach(Box::new(mem), (0x0000, 0xFFFF))
+        .expect("invalid memory map");
+    let mut cpu = Cpu::with_variant(bus, variant);
+    cpu.programm_counter = start;
+    cpu
+}
+
+// runs until the programm counter stops moving, which is how every test signals its result
+// a jam or STP counts as a trap as well
+fn run_to_trap(cpu: &mut Cpu) -> Trap {
+    let mut history = VecDeque::with_capacity(HISTORY);
+    for _ in 0..MAX_INSTRUCTIONS {
+        let pc = cpu.programm_counter;
+        if history.len() == HISTORY {
+            history.pop_front();
+        }
+        history.push_back(Step {
+            pc,
+            opt_code: cpu.peek(pc),
+            registers: [
+                cpu.accumulator,
+                cpu.x,
+                cpu.y,
+                cpu.stack_pointer,
+                cpu.status_flags.to_byte(),
+            ],
+        });
+
+        let stopped = cpu.step().is_err();
+        if stopped || cpu.programm_counter == pc {
+            return Trap { addr: pc, history };
+        }
+    }
+    panic!(
+        "no trap after {MAX_INSTRUCTIONS} instructions\n{}",
+        report(cpu)
+    );
+}
+
+fn report(cpu: &Cpu) -> String {
+    format!(
+        "pc {:04x}  a {:02x}  x {:02x}  y {:02x}  sp {:02x}  p {:08b}  test case {:02x}",
+        cpu.programm_counter,
+        cpu.accumulator,
+        cpu.x,
+        cpu.y,
+        cpu.stack_pointer,
+        cpu.status_flags.to_byte(),
+        cpu.peek(TEST_CASE),
+    )
+}
+
+// the disassembly comes from the memory as it is now, self modifying code may differ
+fn history(cpu: &Cpu, trap: &Trap) -> String {
+    trap.history
+        .iter()
+        .map(|step| {
+            let [a, x, y, sp, p] = step.registers;
+            format!(
+                "{:04x}  {:02x}  {:<12}  a {a:02x}  x {x:02x}  y {y:02x}  sp {sp:02x}  p {p:08b}",
+                step.pc,
+                step.opt_code,
+                disassemble(cpu, step.pc).0,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn assert_trap(cpu: &Cpu, trap: &Trap, success: u16) {
+    assert!(
+        trap.addr == success,
+        "trapped at {:04x} instead of {:04x}\n{}\nlast instructions:\n{}",
+        trap.addr,
+        success,
+        report(cpu),
+        history(cpu, trap),
+    );
+}
+
+#[test]
+#[ignore = "needs tests/roms/6502_functional_test.bin"]
+fn functional_test() {
+    let mut cpu = load(
+        "6502_functional_test.bin",
+        0x0000,
+        0x0400,
+        Variant::Nmos6502,
+    );
+    let trap = run_to_trap(&mut cpu);
+    assert_trap(&cpu, &trap, FUNCTIONAL_SUCCESS);
+}
+
+#[test]
+#[ignore = "needs tests/roms/65C02_extended_opcodes_test.bin"]
+fn extended_opcodes_test() {
+    let mut cpu = load(
+        "65C02_extended_opcodes_test.bin",
+        0x0000,
+        0x0400,
+        Variant::Cmos65C02,
+    );
+    let trap = run_to_trap(&mut cpu);
+    assert_trap(&cpu, &trap, EXTENDED_OPCODES_SUCCESS);
+}
+
+#[test]
+#[ignore = "needs tests/roms/6502_decimal_test.bin"]
+fn decimal_test() {
+    for variant in [Variant::Nmos6502, Variant::Cmos65C02] {
+        let mut cpu = load("6502_decimal_test.bin", 0x0200, 0x0200, variant);
+        // the decimal test always ends in the same place and reports through ERROR
+        let trap = run_to_trap(&mut cpu);
+        assert!(
+            cpu.peek(0x000B) == 0,
+            "{variant:?} failed\n{}\nlast instructions:\n{}",
+            report(&cpu),
+            history(&cpu, &trap),
+        );
+    }
+}
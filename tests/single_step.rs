@@ -0,0 +1,229 @@
+// runner for the per-opcode single step vectors of the ProcessorTests project,
+// https://github.com/SingleStepTests/65x02
+//
+// copy the json files to tests/vectors/<set>/v1/<opcode>.json and run
+// cargo test --test single_step -- --ignored
+// 6502 for the nmos 6502 and wdc65c02 for the 65C02
+// tests/vectors/slice holds the first cases of every opcode of both sets, made with
+// cargo run --example slice_vectors -- <path to 65x02 checkout>
+// tests/vectors/sample holds a few hand written vectors so the runner itself is always tested
+
+use std::path::Path;
+
+use r6502::emulator::{
+    bus::Bus,
+    cpu::{BusAccess, Cpu},
+    error::CpuError,
+    rom::Rom,
+    status::{StatusRegister, BREAK_COMMAND_FLAG, UNUSED_FLAG},
+    variant::Variant,
+};
+use serde_json::Value;
+
+// only the first few mismatches of an opcode are printed
+const SHOWN_FAILURES: usize = 3;
+// nmos opcodes that differ from chip to chip, the emulator refuses them with InvalidOpcode
+const UNSTABLE_OPCODES: [u8; 8] = [0x8B, 0x93, 0x9B, 0x9C, 0x9E, 0x9F, 0xAB, 0xBB];
+// nmos opcodes that lock up the cpu
+const JAM_OPCODES: [u8; 12] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+];
+// STP on the 65C02
+const STP_OPCODE: u8 = 0xDB;
+
+struct State {
+    pc: u16,
+    sp: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+fn number(val: &Value) -> u64 {
+    val.as_u64().expect("not a number")
+}
+
+fn parse_state(val: &Value) -> State {
+    State {
+        pc: number(&val["pc"]) as u16,
+        sp: number(&val["s"]) as u8,
+        a: number(&val["a"]) as u8,
+        x: number(&val["x"]) as u8,
+        y: number(&val["y"]) as u8,
+        p: number(&val["p"]) as u8,
+        ram: val["ram"]
+            .as_array()
+            .expect("ram is not a list")
+            .iter()
+            .map(|cell| (number(&cell[0]) as u16, number(&cell[1]) as u8))
+            .collect(),
+    }
+}
+
+fn parse_cycles(val: &Value) -> Vec<BusAccess> {
+    val.as_array()
+        .expect("cycles is not a list")
+        .iter()
+        .map(|cycle| {
+            let addr = number(&cycle[0]) as u16;
+            let data = number(&cycle[1]) as u8;
+            match cycle[2].as_str() {
+                Some("read") => BusAccess::Read(addr, data),
+                Some("write") => BusAccess::Write(addr, data),
+                other => panic!("unknown bus cycle {other:?}"),
+            }
+        })
+        .collect()
+}
+
+fn cpu_with_state(state: &State, variant: Variant) -> Cpu {
+    let mut bus = Bus::new();
+    bus.attach(Box::new(Rom::new(0x10000)), (0x0000, 0xFFFF))
+        .expect("invalid memory map");
+    let mut cpu = Cpu::with_variant(bus, variant);
+    cpu.illegal_opcodes = true;
+    cpu.programm_counter = state.pc;
+    cpu.stack_pointer = state.sp;
+    cpu.accumulator = state.a;
+    cpu.x = state.x;
+    cpu.y = state.y;
+    cpu.status_flags = StatusRegister::from_byte(state.p);
+    for &(addr, data) in &state.ram {
+        cpu.bus.write_to(addr, data);
+    }
+    cpu
+}
+
+// compares everything the vector expects and describes the first difference
+fn compare(cpu: &Cpu, expected: &State, cycles: &[BusAccess]) -> Result<(), String> {
+    // bit 5 and the break flag only exist on the stack
+    let ignored = UNUSED_FLAG | BREAK_COMMAND_FLAG;
+    let registers = [
+        ("pc", cpu.programm_counter, expected.pc),
+        ("s", cpu.stack_pointer as u16, expected.sp as u16),
+        ("a", cpu.accumulator as u16, expected.a as u16),
+        ("x", cpu.x as u16, expected.x as u16),
+        ("y", cpu.y as u16, expected.y as u16),
+        (
+            "p",
+            (cpu.status_flags.to_byte() & !ignored) as u16,
+            (expected.p & !ignored) as u16,
+        ),
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            return Err(format!("{name} is {actual:02x}, expected {expected:02x}"));
+        }
+    }
+    for &(addr, data) in &expected.ram {
+        let actual = cpu.peek(addr);
+        if actual != data {
+            return Err(format!(
+                "memory at {addr:04x} is {actual:02x}, expected {data:02x}"
+            ));
+        }
+    }
+    if cpu.bus_accesses() != cycles {
+        return Err(format!(
+            "bus cycles\n  actual   {:?}\n  expected {:?}",
+            cpu.bus_accesses(),
+            cycles
+        ));
+    }
+    Ok(())
+}
+
+// the error the emulator raises on purpose for an opcode, the vectors can't be compared there
+fn expected_error(variant: Variant, opt_code: u8, addr: u16) -> Option<CpuError> {
+    match variant {
+        Variant::Nmos6502 if UNSTABLE_OPCODES.contains(&opt_code) => {
+            Some(CpuError::InvalidOpcode { opt_code, addr })
+        }
+        Variant::Nmos6502 if JAM_OPCODES.contains(&opt_code) => Some(CpuError::Jammed { addr }),
+        Variant::Cmos65C02 if opt_code == STP_OPCODE => Some(CpuError::Jammed { addr }),
+        _ => None,
+    }
+}
+
+fn run_set(set: &str, variant: Variant, opt_codes: impl IntoIterator<Item = u8>) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(set)
+        .join("v1");
+
+    let mut failed_opcodes = Vec::new();
+    for opt_code in opt_codes {
+        let path = dir.join(format!("{opt_code:02x}.json"));
+        let text = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("can't read {}: {err}", path.display()));
+        let vectors: Value = serde_json::from_str(&text).expect("invalid json");
+
+        let mut failures = Vec::new();
+        for vector in vectors.as_array().expect("not a list of tests") {
+            let initial = parse_state(&vector["initial"]);
+            let expected = parse_state(&vector["final"]);
+            let cycles = parse_cycles(&vector["cycles"]);
+
+            let mut cpu = cpu_with_state(&initial, variant);
+            let result = cpu.step();
+            match expected_error(variant, opt_code, initial.pc) {
+                Some(err) if result == Err(err) => {}
+                Some(err) => {
+                    failures.push(format!("{}: {result:?}, expected {err}", vector["name"]))
+                }
+                None => match result {
+                    Err(err) => failures.push(format!("{}: {err}", vector["name"])),
+                    Ok(()) => {
+                        if let Err(mismatch) = compare(&cpu, &expected, &cycles) {
+                            failures.push(format!("{}: {mismatch}", vector["name"]));
+                        }
+                    }
+                },
+            }
+        }
+
+        if !failures.is_empty() {
+            eprintln!("opcode {opt_code:02x}: {} failed", failures.len());
+            for failure in failures.iter().take(SHOWN_FAILURES) {
+                eprintln!("  {failure}");
+            }
+            failed_opcodes.push(opt_code);
+        }
+    }
+
+    assert!(
+        failed_opcodes.is_empty(),
+        "{set}: opcodes {failed_opcodes:02x?} failed"
+    );
+}
+
+#[test]
+fn sample() {
+    run_set("sample", Variant::Nmos6502, [0x02, 0x85, 0x8B, 0xA9]);
+}
+
+#[test]
+#[ignore = "needs tests/vectors/slice/6502/v1"]
+fn nmos_6502_slice() {
+    run_set("slice/6502", Variant::Nmos6502, 0..=0xFF);
+}
+
+#[test]
+#[ignore = "needs tests/vectors/slice/wdc65c02/v1"]
+fn wdc_65c02_slice() {
+    run_set("slice/wdc65c02", Variant::Cmos65C02, 0..=0xFF);
+}
+
+#[test]
+#[ignore = "needs tests/vectors/6502/v1"]
+fn nmos_6502() {
+    run_set("6502", Variant::Nmos6502, 0..=0xFF);
+}
+
+#[test]
+#[ignore = "needs tests/vectors/wdc65c02/v1"]
+fn wdc_65c02() {
+    run_set("wdc65c02", Variant::Cmos65C02, 0..=0xFF);
+}